@@ -0,0 +1,124 @@
+//! Region of interest markers for targets measured by [benchpmc].
+//!
+//! When benchpmc is run with `--markers`, events are only counted between a
+//! call to [`start`] and a call to [`stop`], excluding process startup,
+//! dynamic linking and teardown from the measurement:
+//!
+//! ```no_run
+//! extern crate benchpmc_marker;
+//!
+//! # fn load() -> Vec<u64> { vec![] }
+//! # fn work(_: &[u64]) {}
+//! fn main() {
+//!     let input = load();
+//!
+//!     benchpmc_marker::start().unwrap();
+//!     work(&input);
+//!     benchpmc_marker::stop().unwrap();
+//! }
+//! ```
+//!
+//! Both return once benchpmc has started (or stopped) the counters, and do
+//! nothing if the process is not run by benchpmc with `--markers`.
+//!
+//! The markers are sent over a socket at the descriptor named by the
+//! `BENCHPMC_CONTROL_FD` environment variable - write `+` to start and `-` to
+//! stop counting, then read the newline written once benchpmc has done so.
+//! This makes them usable from any language, including sh(1):
+//!
+//! ```text
+//! printf + >&3 && read ack <&3
+//! ```
+//!
+//! [benchpmc]: https://github.com/domodwyer/benchpmc
+//! [`start`]: fn.start.html
+//! [`stop`]: fn.stop.html
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::sync::{Mutex, OnceLock};
+
+/// `CONTROL_ENV` is the environment variable benchpmc sets to the descriptor
+/// of the control socket.
+pub const CONTROL_ENV: &str = "BENCHPMC_CONTROL_FD";
+
+/// Start counting events, returning once the counters are running.
+pub fn start() -> io::Result<()> {
+	send(b'+')
+}
+
+/// Stop counting events, returning once the counters are stopped.
+pub fn stop() -> io::Result<()> {
+	send(b'-')
+}
+
+/// Count events only while f runs, returning its result.
+pub fn region<T, F: FnOnce() -> T>(f: F) -> io::Result<T> {
+	start()?;
+	let v = f();
+	stop()?;
+	Ok(v)
+}
+
+/// Returns the control socket, or None if not run by benchpmc with markers
+/// enabled.
+fn control() -> Option<&'static Mutex<File>> {
+	static CONTROL: OnceLock<Option<Mutex<File>>> = OnceLock::new();
+
+	CONTROL
+		.get_or_init(|| {
+			let fd: RawFd = env::var(CONTROL_ENV).ok()?.parse().ok()?;
+			Some(Mutex::new(unsafe { File::from_raw_fd(fd) }))
+		})
+		.as_ref()
+}
+
+/// Write marker to the control socket and wait for the acknowledgement,
+/// holding the lock so concurrent callers don't read each other's.
+fn send(marker: u8) -> io::Result<()> {
+	let control = match control() {
+		Some(control) => control,
+		None => return Ok(()),
+	};
+
+	let mut socket = control.lock().unwrap_or_else(|e| e.into_inner());
+	socket.write_all(&[marker])?;
+
+	let mut ack = [0];
+	socket.read_exact(&mut ack)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::os::unix::io::IntoRawFd;
+	use std::os::unix::net::UnixStream;
+	use std::thread;
+
+	#[test]
+	fn markers() {
+		let (target, mut benchpmc) = UnixStream::pair().unwrap();
+		env::set_var(CONTROL_ENV, target.into_raw_fd().to_string());
+
+		let handle = thread::spawn(move || {
+			let mut markers = vec![];
+			let mut buf = [0];
+			while benchpmc.read(&mut buf).unwrap() == 1 {
+				markers.push(buf[0]);
+				benchpmc.write_all(b"\n").unwrap();
+				if markers.len() == 4 {
+					break;
+				}
+			}
+			markers
+		});
+
+		start().unwrap();
+		stop().unwrap();
+		assert_eq!(region(|| 42).unwrap(), 42);
+
+		assert_eq!(handle.join().unwrap(), b"+-+-");
+	}
+}
@@ -0,0 +1,152 @@
+use event::Sample;
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// `BASELINE_DIR` is the directory (relative to the working directory) saved
+/// baselines are stored in.
+pub const BASELINE_DIR: &str = ".benchpmc/baselines";
+
+/// `Baseline` is a named set of observed event values persisted between
+/// invocations, used to compare subsequent runs against.
+///
+/// Baselines are stored as one line per event, containing the event name and
+/// a tab, followed by the space separated observed values:
+///
+/// ```text
+/// instructions<TAB>19031333328 19031333301 19031333342
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct Baseline {
+	name: String,
+	events: Vec<(String, Vec<u64>)>,
+}
+
+impl Baseline {
+	/// Load the baseline called name from dir.
+	pub fn load(dir: &Path, name: &str) -> io::Result<Self> {
+		let r = BufReader::new(File::open(path(dir, name)?)?);
+
+		let mut events = vec![];
+		for line in r.lines() {
+			let line = line?;
+			let mut fields = line.splitn(2, '\t');
+
+			let event = fields.next().unwrap_or("");
+			let values = fields
+				.next()
+				.unwrap_or("")
+				.split_whitespace()
+				.map(|v| v.parse::<u64>())
+				.collect::<Result<Vec<_>, _>>()
+				.map_err(|e| {
+					io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", event, e))
+				})?;
+
+			events.push((event.to_string(), values));
+		}
+
+		Ok(Baseline {
+			name: name.to_string(),
+			events,
+		})
+	}
+
+	/// Save the observed values in samples as the baseline called name in dir,
+	/// replacing any existing baseline of the same name.
+	pub fn save(dir: &Path, name: &str, samples: &[Sample]) -> io::Result<PathBuf> {
+		let path = path(dir, name)?;
+		fs::create_dir_all(dir)?;
+
+		let mut w = BufWriter::new(File::create(&path)?);
+		for s in samples {
+			let values: Vec<_> = s.values.iter().map(|v| v.to_string()).collect();
+			writeln!(w, "{}\t{}", s.name, values.join(" "))?;
+		}
+		w.flush()?;
+
+		Ok(path)
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// Returns the values recorded for event, if any.
+	pub fn get(&self, event: &str) -> Option<&[u64]> {
+		self.events
+			.iter()
+			.find(|(name, _)| name == event)
+			.map(|(_, values)| values.as_slice())
+	}
+}
+
+/// Baseline names are used as file names, so they mustn't be able to escape
+/// dir.
+fn path(dir: &Path, name: &str) -> io::Result<PathBuf> {
+	if name.is_empty() || name.starts_with('.') || name.contains('/') || name.contains('\0') {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("invalid baseline name '{}'", name),
+		));
+	}
+	Ok(dir.join(name))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::process;
+
+	#[test]
+	fn test_save_load() {
+		let dir = std::env::temp_dir().join(format!("benchpmc-baseline-{}", process::id()));
+
+		let samples = vec![
+			Sample {
+				name: "instructions",
+				spec: "instructions",
+				values: &[42, 24],
+				relative: None,
+			},
+			Sample {
+				name: "UOPS_RETIRED.ALL,cmask=1",
+				spec: "UOPS_RETIRED.ALL,cmask=1",
+				values: &[1],
+				relative: None,
+			},
+		];
+
+		let path = Baseline::save(&dir, "before", &samples).unwrap();
+		assert_eq!(path, dir.join("before"));
+
+		let b = Baseline::load(&dir, "before").unwrap();
+		assert_eq!(b.name(), "before");
+		assert_eq!(b.get("instructions"), Some(&[42, 24][..]));
+		assert_eq!(b.get("UOPS_RETIRED.ALL,cmask=1"), Some(&[1][..]));
+		assert_eq!(b.get("cycles"), None);
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_bad_name() {
+		let dir = Path::new(BASELINE_DIR);
+		for name in &["", "..", "../escape", ".hidden", "a/b"] {
+			assert_eq!(
+				Baseline::load(dir, name).unwrap_err().kind(),
+				io::ErrorKind::InvalidInput
+			);
+		}
+	}
+
+	#[test]
+	fn test_missing() {
+		let dir = std::env::temp_dir().join(format!("benchpmc-missing-{}", process::id()));
+		assert_eq!(
+			Baseline::load(&dir, "nope").unwrap_err().kind(),
+			io::ErrorKind::NotFound
+		);
+	}
+}
@@ -0,0 +1,47 @@
+/// Parse a list of CPUs such as `0`, `0,2` or `0-3,6` (as used by taskset(1)
+/// and cpuset(1)) into the sorted CPU numbers.
+pub fn parse(s: &str) -> Result<Vec<usize>, String> {
+	let invalid = || format!("invalid CPU list '{}'", s);
+
+	let mut cpus = vec![];
+	for part in s.trim().split(',') {
+		let mut bounds = part.splitn(2, '-').map(|v| v.trim().parse::<usize>());
+		let start = bounds.next().unwrap().map_err(|_| invalid())?;
+		let end = match bounds.next() {
+			Some(end) => end.map_err(|_| invalid())?,
+			None => start,
+		};
+
+		if end < start {
+			return Err(invalid());
+		}
+		cpus.extend(start..=end);
+	}
+
+	cpus.sort_unstable();
+	cpus.dedup();
+	Ok(cpus)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse() {
+		assert_eq!(parse("0"), Ok(vec![0]));
+		assert_eq!(parse("2,0"), Ok(vec![0, 2]));
+		assert_eq!(parse("0-3,6"), Ok(vec![0, 1, 2, 3, 6]));
+		assert_eq!(parse("1-2,2-3"), Ok(vec![1, 2, 3]));
+	}
+
+	#[test]
+	fn test_parse_invalid() {
+		assert!(parse("").is_err());
+		assert!(parse("a").is_err());
+		assert!(parse("1,").is_err());
+		assert!(parse("3-1").is_err());
+		assert!(parse("-1").is_err());
+		assert!(parse("1-2-3").is_err());
+	}
+}
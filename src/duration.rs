@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+/// Units accepted by [parse], and their length in seconds.
+///
+/// [parse]: fn.parse.html
+const UNITS: &[(&str, f64)] = &[
+	("us", 1e-6),
+	("ms", 1e-3),
+	("s", 1.0),
+	("m", 60.0),
+	("h", 3600.0),
+];
+
+/// Parse a human friendly duration such as `500ms`, `1.5s` or `2m` - a number
+/// without a unit is a number of seconds.
+pub fn parse(s: &str) -> Result<Duration, String> {
+	let s = s.trim();
+	let idx = s
+		.find(|c: char| !(c.is_ascii_digit() || c == '.'))
+		.unwrap_or(s.len());
+	let (value, unit) = s.split_at(idx);
+
+	let scale = match unit {
+		"" => 1.0,
+		unit => UNITS
+			.iter()
+			.find(|&&(u, _)| u == unit)
+			.map(|&(_, scale)| scale)
+			.ok_or_else(|| format!("unknown unit '{}' in duration '{}'", unit, s))?,
+	};
+
+	let value = value
+		.parse::<f64>()
+		.map_err(|_| format!("invalid duration '{}'", s))?;
+
+	Duration::try_from_secs_f64(value * scale)
+		.map_err(|_| format!("duration '{}' is out of range", s))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse() {
+		assert_eq!(parse("10"), Ok(Duration::from_secs(10)));
+		assert_eq!(parse("10s"), Ok(Duration::from_secs(10)));
+		assert_eq!(parse("1.5s"), Ok(Duration::from_millis(1500)));
+		assert_eq!(parse("500ms"), Ok(Duration::from_millis(500)));
+		assert_eq!(parse("250us"), Ok(Duration::from_micros(250)));
+		assert_eq!(parse("2m"), Ok(Duration::from_secs(120)));
+		assert_eq!(parse("1h"), Ok(Duration::from_secs(3600)));
+	}
+
+	#[test]
+	fn test_parse_invalid() {
+		assert!(parse("").is_err());
+		assert!(parse("s").is_err());
+		assert!(parse("10x").is_err());
+		assert!(parse("1.2.3s").is_err());
+		assert!(parse("-1s").is_err());
+	}
+
+	#[test]
+	fn test_parse_out_of_range() {
+		assert!(parse("99999999999999999999999").is_err());
+		assert!(parse("99999999999999999999h").is_err());
+		assert!(parse("inf").is_err());
+		assert!(parse("infs").is_err());
+		assert!(parse("NaN").is_err());
+		assert!(parse("nans").is_err());
+	}
+}
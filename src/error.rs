@@ -1,16 +1,42 @@
 #[cfg(target_os = "freebsd")]
 use pmc;
 
+use nix::errno::Errno;
+use nix::sys::signal::Signal;
+use runner::Status;
 use std::fmt;
 use std::ffi::NulError;
 
 #[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
 	#[cfg(test)]
 	MockError,
 	#[cfg(target_os = "freebsd")]
 	PmcError(pmc::error::Error),
+	/// A libpmc call made without pmc-rs failed.
+	#[cfg(target_os = "freebsd")]
+	HwpmcError(Errno),
+	#[cfg(target_os = "linux")]
+	PerfError(Errno),
+	#[cfg(target_os = "linux")]
+	UnknownEvent(String),
 	ExecError(String),
+	/// The target could not be executed.
+	ExecFailed(Errno),
+	/// The child process failed to perform the given set up step before
+	/// executing the target.
+	SetupFailed(&'static str, Errno),
+	/// The target exited with a non-zero exit code.
+	ExitStatus(i32),
+	/// The target was terminated by a signal.
+	Signaled(Signal),
+	/// The target was terminated for exceeding the configured timeout.
+	TimedOut,
+	/// A command run around the target (i.e. "prepare") failed.
+	HookFailed(&'static str, Status),
+	/// A counter operation (attach, start, stop or read) failed.
+	CounterError(&'static str, Box<Error>),
 }
 
 #[cfg(target_os = "freebsd")]
@@ -30,9 +56,32 @@ impl fmt::Display for Error {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		match *self {
 			Error::ExecError(ref e) => write!(f, "{}", e),
+			Error::ExecFailed(ref e) => write!(f, "failed to execute target: {}", e),
+			Error::SetupFailed(step, ref e) => write!(f, "failed to {} for target: {}", step, e),
+			Error::ExitStatus(code) => write!(f, "target exited with status {}", code),
+			Error::Signaled(sig) => write!(f, "target killed by {}", sig.as_str()),
+			Error::TimedOut => write!(f, "target timed out"),
+			Error::HookFailed(hook, status) => write!(f, "{} command {}", hook, status),
+			Error::CounterError(op, ref e) => write!(f, "failed to {} counter: {}", op, e),
 
 			#[cfg(target_os = "freebsd")]
 			Error::PmcError(ref e) => e.fmt(f),
+			#[cfg(target_os = "freebsd")]
+			Error::HwpmcError(ref e) => e.fmt(f),
+
+			#[cfg(target_os = "linux")]
+			Error::PerfError(Errno::EACCES) | Error::PerfError(Errno::EPERM) => write!(
+				f,
+				"permission denied opening counter (see /proc/sys/kernel/perf_event_paranoid)"
+			),
+			#[cfg(target_os = "linux")]
+			Error::PerfError(Errno::ENOENT) | Error::PerfError(Errno::EOPNOTSUPP) => {
+				write!(f, "event not supported by this CPU or kernel")
+			}
+			#[cfg(target_os = "linux")]
+			Error::PerfError(ref e) => e.fmt(f),
+			#[cfg(target_os = "linux")]
+			Error::UnknownEvent(ref e) => write!(f, "unknown event specifier '{}'", e),
 
 			#[cfg(test)]
 			_ => write!(f, "unknown error"),
@@ -1,8 +1,9 @@
 #![cfg(debug_assertions)]
 #![allow(dead_code)]
 
+use baseline::Baseline;
 use error::Error;
-use event::Printable;
+use event::{Breakdown, Group, Printable, Sample, Sampled};
 use runner::Counter;
 
 use separator::Separatable;
@@ -43,11 +44,26 @@ impl<'a> Printable for MockEvent<'a> {
 	fn name(&self) -> &str {
 		self.name
 	}
+	fn spec(&self) -> &str {
+		self.name
+	}
 	fn value(&self) -> u64 {
 		self.value
 	}
 }
 
+impl<'a> Sampled for MockEvent<'a> {
+	fn samples(&self) -> Vec<Sample<'_>> {
+		vec![]
+	}
+	fn compare(&mut self, _baseline: &Baseline) {}
+	fn exclude(&mut self, _run: usize) {}
+	fn subtract(&mut self, _run: usize, _event: &str, _value: u64) {}
+	fn breakdown(&self, _group: Group) -> Vec<Breakdown<'_>> {
+		vec![]
+	}
+}
+
 impl<'a> MockEvent<'a> {
 	pub fn new(name: &'a str, value: u64) -> Self {
 		MockEvent { name, value }
@@ -1,18 +1,202 @@
+mod perf_event;
 mod pmc_event;
 mod printers;
 
-#[cfg(debug_assertions)]
+#[cfg(all(
+	debug_assertions,
+	not(any(target_os = "freebsd", target_os = "linux"))
+))]
 mod mock_event;
-#[cfg(debug_assertions)]
+#[cfg(all(
+	debug_assertions,
+	not(any(target_os = "freebsd", target_os = "linux"))
+))]
 pub use self::mock_event::MockEvent;
 
 #[cfg(target_os = "freebsd")]
 pub use self::pmc_event::PmcEvent;
 
+#[cfg(target_os = "linux")]
+pub use self::perf_event::PerfEvent;
+
 pub use self::printers::RelativePrinter;
 pub use self::printers::RSDPrinter;
 
+use baseline::Baseline;
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
 pub trait Printable {
 	fn name(&self) -> &str;
+	fn spec(&self) -> &str;
 	fn value(&self) -> u64;
+
+	/// Returns the value observed by the last read of the counter for each
+	/// process and thread of the target, if the counter is configured to
+	/// record them.
+	fn tasks(&self) -> &[Task] {
+		&[]
+	}
+}
+
+/// `Task` is the value of an event counted in a single thread of the target,
+/// or of a process started by it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+	pub pid: u32,
+	pub tid: u32,
+	/// The command name of the process (i.e. of its main thread).
+	pub process: String,
+	/// The command name of the thread.
+	pub thread: String,
+	pub value: u64,
+}
+
+/// `Group` selects how the values of the tasks of the target are grouped in a
+/// [`Breakdown`].
+///
+/// [`Breakdown`]: struct.Breakdown.html
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Group {
+	/// Group threads by the name of their process.
+	Process,
+	/// Group threads by their own name.
+	Thread,
+}
+
+impl FromStr for Group {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"process" => Ok(Group::Process),
+			"thread" => Ok(Group::Thread),
+			_ => Err(format!("unknown breakdown '{}'", s)),
+		}
+	}
+}
+
+/// `Breakdown` is the mean value of an event per process or thread of the
+/// target, over all runs.
+#[derive(Debug, PartialEq)]
+pub struct Breakdown<'a> {
+	/// The event alias if set, otherwise the event specifier.
+	pub name: &'a str,
+	/// The name of each process or thread and the mean value counted in it,
+	/// largest first.
+	pub tasks: Vec<(String, f64)>,
+}
+
+/// Returns the mean value of the tasks observed in each run, grouped by
+/// process or thread name and ordered by value (largest first).
+pub fn breakdown(runs: &[Vec<Task>], group: Group) -> Vec<(String, f64)> {
+	let mut sums: BTreeMap<&str, u64> = BTreeMap::new();
+	for task in runs.iter().flatten() {
+		let name = match group {
+			Group::Process => &task.process,
+			Group::Thread => &task.thread,
+		};
+		*sums.entry(name).or_insert(0) += task.value;
+	}
+
+	let mut tasks: Vec<(String, f64)> = sums
+		.into_iter()
+		.map(|(name, sum)| (name.to_string(), sum as f64 / runs.len() as f64))
+		.collect();
+	tasks.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+	tasks
+}
+
+/// `Sample` holds the raw values observed for a single event, one per run.
+#[derive(Debug, PartialEq)]
+pub struct Sample<'a> {
+	/// The event alias if set, otherwise the event specifier.
+	pub name: &'a str,
+	pub spec: &'a str,
+	pub values: &'a [u64],
+	pub relative: Option<Relative<'a>>,
+}
+
+/// `Relative` describes an event value expressed as a percentage of another
+/// event, as displayed by [`RelativePrinter`].
+///
+/// [`RelativePrinter`]: struct.RelativePrinter.html
+#[derive(Debug, PartialEq)]
+pub struct Relative<'a> {
+	pub to: &'a str,
+	pub percent: f64,
+}
+
+impl<'a> Sample<'a> {
+	/// Returns the alias of the event, if one was set.
+	pub fn alias(&self) -> Option<&'a str> {
+		if self.name == self.spec {
+			return None;
+		}
+		Some(self.name)
+	}
+}
+
+/// `Sampled` exposes the raw observed values of a counter, or of each counter
+/// in a group of counters.
+pub trait Sampled {
+	fn samples(&self) -> Vec<Sample<'_>>;
+
+	/// Compare the observed values against those recorded for the same event
+	/// in baseline when displayed.
+	fn compare(&mut self, baseline: &Baseline);
+
+	/// Discard the value observed in the given run (zero-indexed), as if the
+	/// run never happened.
+	fn exclude(&mut self, run: usize);
+
+	/// Subtract value from the value of the named event observed in the given
+	/// run (zero-indexed), saturating at zero.
+	fn subtract(&mut self, run: usize, event: &str, value: u64);
+
+	/// Returns the mean value of each event per process or thread of the
+	/// target, for counters configured to record them.
+	fn breakdown(&self, group: Group) -> Vec<Breakdown<'_>>;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn task(pid: u32, tid: u32, process: &str, thread: &str, value: u64) -> Task {
+		Task {
+			pid,
+			tid,
+			process: process.to_string(),
+			thread: thread.to_string(),
+			value,
+		}
+	}
+
+	#[test]
+	fn test_breakdown() {
+		let runs = vec![
+			vec![
+				task(1, 1, "sh", "sh", 10),
+				task(2, 2, "gzip", "gzip", 100),
+				task(2, 3, "gzip", "worker", 50),
+			],
+			vec![task(4, 4, "sh", "sh", 20), task(5, 5, "gzip", "gzip", 300)],
+		];
+
+		assert_eq!(
+			breakdown(&runs, Group::Process),
+			vec![(String::from("gzip"), 225.0), (String::from("sh"), 15.0)]
+		);
+		assert_eq!(
+			breakdown(&runs, Group::Thread),
+			vec![
+				(String::from("gzip"), 200.0),
+				(String::from("worker"), 25.0),
+				(String::from("sh"), 15.0),
+			]
+		);
+		assert_eq!(breakdown(&[], Group::Thread), vec![]);
+	}
 }
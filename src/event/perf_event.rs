@@ -0,0 +1,813 @@
+#![cfg(target_os = "linux")]
+
+use event::{Printable, Task};
+
+use error::Error;
+use nix::errno::Errno;
+use nix::libc;
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use nix::unistd::{close, read, sysconf, SysconfVar};
+use runner::Counter;
+use separator::Separatable;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::sync::atomic::{fence, Ordering};
+
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_TYPE_SOFTWARE: u32 = 1;
+const PERF_TYPE_RAW: u32 = 4;
+
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+const PERF_EVENT_IOC_RESET: libc::c_ulong = 0x2403;
+const PERF_EVENT_IOC_SET_OUTPUT: libc::c_ulong = 0x2405;
+
+/// Bit offsets into the `perf_event_attr` flags bitfield.
+const ATTR_DISABLED: u64 = 1 << 0;
+const ATTR_INHERIT: u64 = 1 << 1;
+const ATTR_EXCLUDE_USER: u64 = 1 << 4;
+const ATTR_EXCLUDE_KERNEL: u64 = 1 << 5;
+const ATTR_EXCLUDE_HV: u64 = 1 << 6;
+const ATTR_COMM: u64 = 1 << 9;
+const ATTR_INHERIT_STAT: u64 = 1 << 11;
+
+/// Values read alongside the count (`read_format`), used to scale the count of
+/// an event that was multiplexed with others onto the hardware counters.
+const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+
+/// Record types written to the ring buffer (`enum perf_event_type`).
+const PERF_RECORD_COMM: u32 = 3;
+const PERF_RECORD_READ: u32 = 8;
+
+/// Number of pages in the ring buffer used to record the value of each task,
+/// which must be a power of two. Each task that exits writes ~56 bytes, so
+/// this is enough for ~600 processes or threads per run.
+const RING_PAGES: usize = 8;
+
+/// Generalised event names understood by `perf_event_open(2)`, mirroring the
+/// names used by perf-list(1).
+const EVENTS: &[(&str, u32, u64)] = &[
+	("cpu-cycles", PERF_TYPE_HARDWARE, 0),
+	("cycles", PERF_TYPE_HARDWARE, 0),
+	("instructions", PERF_TYPE_HARDWARE, 1),
+	("cache-references", PERF_TYPE_HARDWARE, 2),
+	("cache-misses", PERF_TYPE_HARDWARE, 3),
+	("branch-instructions", PERF_TYPE_HARDWARE, 4),
+	("branches", PERF_TYPE_HARDWARE, 4),
+	("branch-misses", PERF_TYPE_HARDWARE, 5),
+	("bus-cycles", PERF_TYPE_HARDWARE, 6),
+	("stalled-cycles-frontend", PERF_TYPE_HARDWARE, 7),
+	("stalled-cycles-backend", PERF_TYPE_HARDWARE, 8),
+	("ref-cycles", PERF_TYPE_HARDWARE, 9),
+	("cpu-clock", PERF_TYPE_SOFTWARE, 0),
+	("task-clock", PERF_TYPE_SOFTWARE, 1),
+	("page-faults", PERF_TYPE_SOFTWARE, 2),
+	("faults", PERF_TYPE_SOFTWARE, 2),
+	("context-switches", PERF_TYPE_SOFTWARE, 3),
+	("cs", PERF_TYPE_SOFTWARE, 3),
+	("cpu-migrations", PERF_TYPE_SOFTWARE, 4),
+	("migrations", PERF_TYPE_SOFTWARE, 4),
+	("minor-faults", PERF_TYPE_SOFTWARE, 5),
+	("major-faults", PERF_TYPE_SOFTWARE, 6),
+	("alignment-faults", PERF_TYPE_SOFTWARE, 7),
+	("emulation-faults", PERF_TYPE_SOFTWARE, 8),
+];
+
+/// `PerfEventAttr` is the `struct perf_event_attr` passed to
+/// `perf_event_open(2)` (`PERF_ATTR_SIZE_VER5` layout).
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct PerfEventAttr {
+	type_: u32,
+	size: u32,
+	config: u64,
+	sample_period: u64,
+	sample_type: u64,
+	read_format: u64,
+	flags: u64,
+	wakeup_events: u32,
+	bp_type: u32,
+	config1: u64,
+	config2: u64,
+	branch_sample_type: u64,
+	sample_regs_user: u64,
+	sample_stack_user: u32,
+	clockid: i32,
+	sample_regs_intr: u64,
+	aux_watermark: u32,
+	sample_max_stack: u16,
+	reserved: u16,
+}
+
+#[derive(Debug)]
+/// `PerfEvent` is a per-process counter opened through the Linux
+/// `perf_event_open(2)` interface, and provides output formatting of the
+/// counter values.
+///
+/// Event specifiers are the generalised perf-list(1) names (`instructions`,
+/// `task-clock`, `context-switches`, ...) or raw `rNNNN` hex event codes,
+/// optionally followed by a `:u` or `:k` modifier to only count user or kernel
+/// mode.
+///
+/// A `PerfEvent` records the counter value every time the [set] method is
+/// called.
+///
+/// [set]: #method.set
+///
+pub struct PerfEvent<'a> {
+	spec: &'a str,
+	alias: Option<&'a str>,
+	value: Option<u64>,
+	attr: PerfEventAttr,
+	/// The counter opened for each thread of the process, along with the total
+	/// time it was enabled and running at the last read - unlike the count,
+	/// these are not reset.
+	fds: Vec<(RawFd, (u64, u64))>,
+
+	/// The process the counter is attached to.
+	pid: u32,
+	/// The ring buffer the value of each task is written to as it exits, when
+	/// recording the breakdown.
+	ring: Option<Ring>,
+	tasks: Vec<Task>,
+}
+
+impl<'a> PerfEvent<'a> {
+	pub fn new(spec: &'a str) -> Result<Self, Error> {
+		let (name, modifiers) = match spec.find(':') {
+			Some(idx) => (&spec[..idx], &spec[idx + 1..]),
+			None => (spec, ""),
+		};
+
+		let (type_, config) = parse_event(name)?;
+
+		let mut attr = PerfEventAttr {
+			type_,
+			config,
+			size: mem::size_of::<PerfEventAttr>() as u32,
+			read_format: PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING,
+			flags: ATTR_DISABLED,
+			..Default::default()
+		};
+
+		if !modifiers.is_empty() {
+			// Exclude everything that hasn't been explicitly asked for, as
+			// perf-stat(1) does.
+			attr.flags |= ATTR_EXCLUDE_USER | ATTR_EXCLUDE_KERNEL | ATTR_EXCLUDE_HV;
+			for m in modifiers.chars() {
+				match m {
+					'u' => attr.flags &= !ATTR_EXCLUDE_USER,
+					'k' => attr.flags &= !ATTR_EXCLUDE_KERNEL,
+					'h' => attr.flags &= !ATTR_EXCLUDE_HV,
+					_ => return Err(Error::UnknownEvent(spec.to_string())),
+				}
+			}
+		}
+
+		// Open (and immediately close) a counter for this process to ensure
+		// the event is supported by the running kernel and CPU.
+		let fd = open(&attr, 0)?;
+		let _ = close(fd);
+
+		Ok(PerfEvent {
+			spec,
+			attr,
+			alias: None,
+			value: None,
+			fds: Vec::new(),
+			pid: 0,
+			ring: None,
+			tasks: Vec::new(),
+		})
+	}
+
+	/// Set an alternative (human friendly) name for the configured event,
+	/// displayed when printing the counter value instead of the raw event name.
+	pub fn alias(mut self, alias: &'a str) -> Self {
+		self.alias = Some(alias);
+		self
+	}
+
+	/// Count events in all child processes and threads created by the
+	/// process the counter is attached to, as well as the process itself.
+	pub fn inherit(mut self, inherit: bool) -> Self {
+		if inherit {
+			self.attr.flags |= ATTR_INHERIT;
+		} else {
+			self.attr.flags &= !ATTR_INHERIT;
+		}
+		self
+	}
+
+	/// Record the value of the event in each process and thread of the target
+	/// (implies [inherit]), available from [`Printable::tasks`] after each read.
+	///
+	/// The value of each child process and thread is recorded when it exits
+	/// (so a task that outlives the target is not broken down), and the
+	/// remainder of the total is attributed to the target itself.
+	///
+	/// [inherit]: #method.inherit
+	/// [`Printable::tasks`]: ../trait.Printable.html#method.tasks
+	pub fn breakdown(mut self, breakdown: bool) -> Self {
+		let flags = ATTR_INHERIT | ATTR_INHERIT_STAT | ATTR_COMM;
+		if breakdown {
+			self.attr.flags |= flags;
+		} else {
+			self.attr.flags &= !(ATTR_INHERIT_STAT | ATTR_COMM);
+		}
+		self
+	}
+
+	/// Apply the ioctl request to the counter of every thread.
+	fn ioctl(&self, request: libc::c_ulong) -> Result<(), Error> {
+		if self.fds.is_empty() {
+			return Err(Error::PerfError(Errno::EBADF));
+		}
+		for &(fd, _) in &self.fds {
+			ioctl(fd, request)?;
+		}
+		Ok(())
+	}
+
+	/// Release the counters (and ring buffer) attached to a process.
+	fn close(&mut self) {
+		self.ring = None;
+		for (fd, _) in self.fds.drain(..) {
+			let _ = close(fd);
+		}
+	}
+}
+
+fn ioctl(fd: RawFd, request: libc::c_ulong) -> Result<(), Error> {
+	let ret = unsafe { libc::ioctl(fd, request as _, 0) };
+	if ret == -1 {
+		return Err(Error::PerfError(Errno::last()));
+	}
+	Ok(())
+}
+
+/// Returns the threads of the running process pid, starting with pid itself.
+///
+/// Only pid is returned if the threads can't be listed (i.e. pid is 0 for the
+/// calling thread).
+fn threads(pid: u32) -> Vec<u32> {
+	let mut tids: Vec<u32> = fs::read_dir(format!("/proc/{}/task", pid))
+		.map(|dir| {
+			dir.filter_map(|e| e.ok()?.file_name().to_str()?.parse().ok())
+				.filter(|&tid| tid != pid)
+				.collect()
+		})
+		.unwrap_or_default();
+	tids.sort_unstable();
+	tids.insert(0, pid);
+	tids
+}
+
+/// Read the count of the event fd, along with the total time it was enabled and
+/// running.
+fn read_count(fd: RawFd) -> Result<(u64, u64, u64), Error> {
+	let mut buf = [0; 24];
+	match read(fd, &mut buf) {
+		Ok(24) => (),
+		Ok(_) => return Err(Error::PerfError(Errno::EIO)),
+		Err(e) => return Err(Error::PerfError(e.as_errno().unwrap_or(Errno::EIO))),
+	}
+
+	let u64_at = |i: usize| {
+		let mut b = [0; 8];
+		b.copy_from_slice(&buf[i..i + 8]);
+		u64::from_ne_bytes(b)
+	};
+	Ok((u64_at(0), u64_at(8), u64_at(16)))
+}
+
+/// Resolve an event name into a `perf_event_attr` type and config pair.
+fn parse_event(name: &str) -> Result<(u32, u64), Error> {
+	if let Some(&(_, type_, config)) = EVENTS.iter().find(|&&(n, _, _)| n == name) {
+		return Ok((type_, config));
+	}
+
+	// Raw event codes, i.e. r01c2
+	if name.starts_with('r') && name.len() > 1 {
+		if let Ok(config) = u64::from_str_radix(&name[1..], 16) {
+			return Ok((PERF_TYPE_RAW, config));
+		}
+	}
+
+	Err(Error::UnknownEvent(name.to_string()))
+}
+
+/// Open a counter described by attr for pid, on any CPU.
+fn open(attr: &PerfEventAttr, pid: libc::pid_t) -> Result<RawFd, Error> {
+	let fd = unsafe {
+		libc::syscall(
+			libc::SYS_perf_event_open,
+			attr as *const PerfEventAttr,
+			pid,
+			-1 as libc::c_int,
+			-1 as libc::c_int,
+			PERF_FLAG_FD_CLOEXEC,
+		)
+	};
+
+	if fd == -1 {
+		return Err(Error::PerfError(Errno::last()));
+	}
+
+	Ok(fd as RawFd)
+}
+
+impl<'a> Counter for PerfEvent<'a> {
+	/// Attach a counter to every thread of pid (as perf-stat(1) does with
+	/// `-p`), so the threads of an already running process are counted too -
+	/// threads created after attaching are only counted when inherited.
+	fn attach(&mut self, pid: u32) -> Result<(), Error> {
+		// Each run is a new process, so release the counters attached to the
+		// previous one.
+		self.close();
+		self.pid = pid;
+
+		for tid in threads(pid) {
+			match open(&self.attr, tid as libc::pid_t) {
+				Ok(fd) => self.fds.push((fd, (0, 0))),
+				// The thread exited after it was listed
+				Err(Error::PerfError(Errno::ESRCH)) if tid != pid => continue,
+				Err(e) => {
+					self.close();
+					return Err(e);
+				}
+			}
+		}
+
+		// Only the main thread records the breakdown, as the records of
+		// another task's event can't be redirected to the ring buffer
+		if self.attr.flags & ATTR_INHERIT_STAT != 0 {
+			let fd = self.fds[0].0;
+			self.ring = Some(Ring::open(&self.attr, pid as libc::pid_t, fd)?);
+		}
+		Ok(())
+	}
+
+	fn start(&mut self) -> Result<(), Error> {
+		self.ioctl(PERF_EVENT_IOC_ENABLE)
+	}
+
+	fn stop(&mut self) -> Result<(), Error> {
+		self.ioctl(PERF_EVENT_IOC_DISABLE)
+	}
+
+	/// Reads the current counter value (the sum of every thread) and resets
+	/// the counter.
+	///
+	/// If the event was multiplexed with other events (i.e. more events were
+	/// opened than there are hardware counters) the value is scaled up to
+	/// estimate the count over the whole run, as perf-stat(1) does.
+	///
+	/// perf counters can only be reset to zero, so any other value is
+	/// rejected.
+	fn set(&mut self, value: u64) -> Result<u64, Error> {
+		if value != 0 {
+			return Err(Error::PerfError(Errno::EINVAL));
+		}
+
+		if self.fds.is_empty() {
+			return Err(Error::PerfError(Errno::EBADF));
+		}
+
+		let mut v: u64 = 0;
+		for &mut (fd, ref mut times) in &mut self.fds {
+			let (count, enabled, running) = read_count(fd)?;
+			ioctl(fd, PERF_EVENT_IOC_RESET)?;
+
+			v = v.saturating_add(scale(
+				count,
+				enabled.saturating_sub(times.0),
+				running.saturating_sub(times.1),
+			));
+			*times = (enabled, running);
+		}
+		self.value = Some(v);
+
+		if let Some(ref mut ring) = self.ring {
+			self.tasks = tasks(self.pid, v, &ring.drain());
+		}
+		Ok(v)
+	}
+}
+
+/// Scale value, counted while the event was running on a hardware counter, to
+/// the whole time it was enabled.
+fn scale(value: u64, enabled: u64, running: u64) -> u64 {
+	if running == 0 || running >= enabled {
+		return value;
+	}
+	(value as u128 * enabled as u128 / running as u128) as u64
+}
+
+/// Returns the value of each task recorded in the ring buffer data, along with
+/// the target (the task pid) which is attributed the remainder of total.
+fn tasks(pid: u32, total: u64, data: &[u8]) -> Vec<Task> {
+	let u16_at = |i: usize| u16::from_ne_bytes([data[i], data[i + 1]]);
+	let u32_at = |i: usize| {
+		let mut b = [0; 4];
+		b.copy_from_slice(&data[i..i + 4]);
+		u32::from_ne_bytes(b)
+	};
+	let u64_at = |i: usize| {
+		let mut b = [0; 8];
+		b.copy_from_slice(&data[i..i + 8]);
+		u64::from_ne_bytes(b)
+	};
+
+	// The command name of each thread, changed on exec
+	let mut comms: HashMap<u32, String> = HashMap::new();
+	// The pid, tid and value of each task in the order they exited
+	let mut values = vec![];
+
+	let mut i = 0;
+	while i + 8 <= data.len() {
+		let (type_, size) = (u32_at(i), u16_at(i + 6) as usize);
+		if size < 8 || i + size > data.len() {
+			break;
+		}
+
+		match type_ {
+			PERF_RECORD_COMM if size >= 16 => {
+				let comm = &data[i + 16..i + size];
+				let len = comm.iter().position(|&c| c == 0).unwrap_or(comm.len());
+				let comm = String::from_utf8_lossy(&comm[..len]).into_owned();
+				comms.insert(u32_at(i + 12), comm);
+			}
+			PERF_RECORD_READ if size >= 24 => {
+				values.push((u32_at(i + 8), u32_at(i + 12), u64_at(i + 16)));
+			}
+			_ => (),
+		}
+
+		i += size;
+	}
+
+	let counted: u64 = values.iter().map(|&(_, _, v)| v).sum();
+	values.push((pid, pid, total.saturating_sub(counted)));
+
+	// A task that never changed its name shares the name of its process, or
+	// of the target it was (eventually) forked from
+	let name = |ids: &[u32]| {
+		ids.iter()
+			.filter_map(|id| comms.get(id))
+			.next()
+			.cloned()
+			.unwrap_or_else(|| String::from("?"))
+	};
+	let root = pid;
+	values
+		.into_iter()
+		.map(|(pid, tid, value)| Task {
+			pid,
+			tid,
+			process: name(&[pid, root]),
+			thread: name(&[tid, pid, root]),
+			value,
+		})
+		.collect()
+}
+
+/// `Ring` is the ring buffer of a perf event mapped into memory, to which the
+/// kernel writes records.
+#[derive(Debug)]
+struct Ring {
+	fd: RawFd,
+	base: *mut u8,
+	page: usize,
+}
+
+impl Ring {
+	/// Map a ring buffer for the records of the event fd, opened with attr for
+	/// pid.
+	///
+	/// The kernel refuses to map the buffer of an inherited per-process event,
+	/// so the records are redirected to the buffer of a disabled copy of the
+	/// event (which doesn't occupy a hardware counter) opened without
+	/// inheritance.
+	fn open(attr: &PerfEventAttr, pid: libc::pid_t, fd: RawFd) -> Result<Ring, Error> {
+		let mut attr = *attr;
+		attr.flags &= !(ATTR_INHERIT | ATTR_INHERIT_STAT | ATTR_COMM);
+		attr.flags |= ATTR_DISABLED;
+		let out = open(&attr, pid)?;
+
+		let ring = Ring::map(out).inspect_err(|_| {
+			let _ = close(out);
+		})?;
+
+		let ret = unsafe { libc::ioctl(fd, PERF_EVENT_IOC_SET_OUTPUT as _, out) };
+		if ret == -1 {
+			return Err(Error::PerfError(Errno::last()));
+		}
+
+		Ok(ring)
+	}
+
+	/// Map the ring buffer of the event fd, taking ownership of fd.
+	fn map(fd: RawFd) -> Result<Ring, Error> {
+		let page = sysconf(SysconfVar::PAGE_SIZE)
+			.ok()
+			.and_then(|v| v)
+			.unwrap_or(4096) as usize;
+
+		let base = unsafe {
+			mmap(
+				ptr::null_mut(),
+				(RING_PAGES + 1) * page,
+				ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+				MapFlags::MAP_SHARED,
+				fd,
+				0,
+			)
+		}
+		.map_err(|e| Error::PerfError(e.as_errno().unwrap_or(Errno::EIO)))?;
+
+		Ok(Ring {
+			fd,
+			base: base as *mut u8,
+			page,
+		})
+	}
+
+	/// Returns the records written since the last call, in order.
+	fn drain(&mut self) -> Vec<u8> {
+		// The first page is the `struct perf_event_mmap_page` header, with the
+		// data_head and data_tail offsets at 1024 and 1032
+		let head_ptr = unsafe { self.base.add(1024) as *const u64 };
+		let tail_ptr = unsafe { self.base.add(1032) as *mut u64 };
+
+		let head = unsafe { ptr::read_volatile(head_ptr) };
+		fence(Ordering::Acquire);
+		let tail = unsafe { ptr::read_volatile(tail_ptr) };
+
+		let size = RING_PAGES * self.page;
+		let data = unsafe { self.base.add(self.page) };
+		let out = (tail..head)
+			.map(|i| unsafe { *data.add(i as usize % size) })
+			.collect();
+
+		fence(Ordering::Release);
+		unsafe { ptr::write_volatile(tail_ptr, head) };
+		out
+	}
+}
+
+impl Drop for Ring {
+	fn drop(&mut self) {
+		let _ = unsafe {
+			munmap(
+				self.base as *mut libc::c_void,
+				(RING_PAGES + 1) * self.page,
+			)
+		};
+		let _ = close(self.fd);
+	}
+}
+
+impl<'a> Drop for PerfEvent<'a> {
+	fn drop(&mut self) {
+		self.close();
+	}
+}
+
+impl<'a> fmt::Display for PerfEvent<'a> {
+	/// Prints the counter name (or alias) and value in the format:
+	///
+	/// ```text
+	///                     task-clock:      1,503,302
+	/// ```
+	///
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{:>30}: {:>14}",
+			self.alias.unwrap_or(self.spec),
+			self.value.unwrap_or(0).separated_string(),
+		)
+	}
+}
+
+impl<'a> Printable for PerfEvent<'a> {
+	fn name(&self) -> &str {
+		self.alias.unwrap_or(self.spec)
+	}
+	fn spec(&self) -> &str {
+		self.spec
+	}
+	fn value(&self) -> u64 {
+		self.value.unwrap_or(0)
+	}
+	fn tasks(&self) -> &[Task] {
+		&self.tasks
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse() {
+		assert_eq!(parse_event("instructions"), Ok((PERF_TYPE_HARDWARE, 1)));
+		assert_eq!(parse_event("task-clock"), Ok((PERF_TYPE_SOFTWARE, 1)));
+		assert_eq!(parse_event("r01c2"), Ok((PERF_TYPE_RAW, 0x01c2)));
+		assert_eq!(
+			parse_event("not-an-event"),
+			Err(Error::UnknownEvent(String::from("not-an-event")))
+		);
+		assert_eq!(parse_event("r"), Err(Error::UnknownEvent(String::from("r"))));
+	}
+
+	#[test]
+	fn test_modifiers() {
+		assert_eq!(
+			PerfEvent::new("task-clock:x").unwrap_err(),
+			Error::UnknownEvent(String::from("task-clock:x"))
+		);
+	}
+
+	#[test]
+	#[ignore]
+	fn test_event() {
+		let mut event = PerfEvent::new("task-clock").unwrap();
+
+		assert_eq!(event.spec, "task-clock");
+		assert_eq!(event.alias, None);
+		assert_eq!(event.value(), 0);
+
+		// Count the calling thread
+		assert!(event.attach(0).is_ok());
+		assert!(event.start().is_ok());
+		let _ = (0..100_000).fold(0u64, |acc, v| acc.wrapping_add(v));
+		assert!(event.stop().is_ok());
+
+		let v = event.set(0).unwrap();
+		assert!(v > 0);
+		assert_eq!(event.value(), v);
+
+		assert_eq!(event.set(42), Err(Error::PerfError(Errno::EINVAL)));
+	}
+
+	#[test]
+	#[ignore]
+	fn test_attach_threads() {
+		use std::sync::atomic::AtomicBool;
+		use std::sync::Arc;
+		use std::thread;
+		use std::time::Duration;
+
+		// Threads already running when the counter is attached are counted,
+		// not just the main thread of the process
+		let stop = Arc::new(AtomicBool::new(false));
+		let workers: Vec<_> = (0..2)
+			.map(|_| {
+				let stop = stop.clone();
+				thread::spawn(move || while !stop.load(Ordering::Relaxed) {})
+			})
+			.collect();
+
+		let mut event = PerfEvent::new("task-clock").unwrap();
+		assert!(event.attach(std::process::id()).is_ok());
+		assert!(event.fds.len() > 2);
+
+		assert!(event.start().is_ok());
+		thread::sleep(Duration::from_millis(200));
+		assert!(event.stop().is_ok());
+
+		stop.store(true, Ordering::Relaxed);
+		for w in workers {
+			w.join().unwrap();
+		}
+
+		// At least half of the interval was spent in the workers
+		assert!(event.set(0).unwrap() > 100_000_000);
+	}
+
+	#[test]
+	fn test_threads() {
+		let pid = std::process::id();
+		let tids = threads(pid);
+		assert_eq!(tids[0], pid);
+
+		// Including the thread running this test
+		let tid = unsafe { libc::syscall(libc::SYS_gettid) } as u32;
+		assert!(tids.contains(&tid));
+
+		assert_eq!(threads(0), vec![0]);
+	}
+
+	#[test]
+	fn test_alias() {
+		let event = PerfEvent {
+			spec: "task-clock",
+			alias: None,
+			value: None,
+			attr: PerfEventAttr::default(),
+			fds: Vec::new(),
+			pid: 0,
+			ring: None,
+			tasks: Vec::new(),
+		}
+		.alias("alias");
+
+		assert_eq!(event.spec, "task-clock");
+		assert_eq!(event.alias, Some("alias"));
+		assert_eq!(event.name(), "alias");
+	}
+
+	#[test]
+	fn test_inherit() {
+		let event = PerfEvent {
+			spec: "task-clock",
+			alias: None,
+			value: None,
+			attr: PerfEventAttr::default(),
+			fds: Vec::new(),
+			pid: 0,
+			ring: None,
+			tasks: Vec::new(),
+		};
+
+		let event = event.inherit(true);
+		assert_eq!(event.attr.flags & ATTR_INHERIT, ATTR_INHERIT);
+
+		let event = event.inherit(false);
+		assert_eq!(event.attr.flags & ATTR_INHERIT, 0);
+	}
+
+	#[test]
+	fn test_scale() {
+		assert_eq!(scale(100, 10, 10), 100);
+		assert_eq!(scale(100, 10, 5), 200);
+		assert_eq!(scale(100, 0, 0), 100);
+		assert_eq!(scale(u64::MAX / 2, 4, 2), u64::MAX - 1);
+	}
+
+	/// Returns a record of type_ with the given body, as written by the kernel.
+	fn record(type_: u32, body: &[u8]) -> Vec<u8> {
+		let mut r = type_.to_ne_bytes().to_vec();
+		r.extend(&0u16.to_ne_bytes());
+		r.extend(&((body.len() + 8) as u16).to_ne_bytes());
+		r.extend(body);
+		r
+	}
+
+	fn comm(pid: u32, tid: u32, comm: &str) -> Vec<u8> {
+		let mut body = pid.to_ne_bytes().to_vec();
+		body.extend(&tid.to_ne_bytes());
+		body.extend(comm.as_bytes());
+		body.resize(8 + (comm.len() / 8 + 1) * 8, 0);
+		record(PERF_RECORD_COMM, &body)
+	}
+
+	fn read_value(pid: u32, tid: u32, value: u64) -> Vec<u8> {
+		let mut body = pid.to_ne_bytes().to_vec();
+		body.extend(&tid.to_ne_bytes());
+		body.extend(&value.to_ne_bytes());
+		record(PERF_RECORD_READ, &body)
+	}
+
+	#[test]
+	fn test_tasks() {
+		let mut data = comm(10, 10, "sh");
+		data.extend(comm(11, 11, "gzip"));
+		data.extend(comm(11, 13, "worker"));
+		data.extend(read_value(11, 13, 50));
+		data.extend(read_value(11, 11, 100));
+		// Forked without exec, so named after the target
+		data.extend(read_value(12, 12, 5));
+
+		let task = |pid, tid, process: &str, thread: &str, value| Task {
+			pid,
+			tid,
+			process: process.to_string(),
+			thread: thread.to_string(),
+			value,
+		};
+
+		assert_eq!(
+			tasks(10, 200, &data),
+			vec![
+				task(11, 13, "gzip", "worker", 50),
+				task(11, 11, "gzip", "gzip", 100),
+				task(12, 12, "sh", "sh", 5),
+				task(10, 10, "sh", "sh", 45),
+			]
+		);
+
+		// A truncated record is ignored
+		data.truncate(data.len() - 4);
+		assert_eq!(tasks(10, 200, &data).len(), 3);
+	}
+}
@@ -1,12 +1,16 @@
 #![cfg(target_os = "freebsd")]
 
 extern crate pmc;
+extern crate pmc_sys;
 
 use event::Printable;
 
 use error::Error;
+use nix::errno::Errno;
+use nix::libc;
 use runner::Counter;
 use separator::Separatable;
+use std::ffi::CString;
 use std::fmt;
 
 #[derive(Debug)]
@@ -24,7 +28,16 @@ pub struct PmcEvent<'a> {
 	spec: &'a str,
 	alias: Option<&'a str>,
 	value: Option<u64>,
-	counter: pmc::Counter<'a>,
+	counter: Pmc<'a>,
+}
+
+/// `Pmc` is the process-scoped PMC counting the event.
+#[derive(Debug)]
+enum Pmc<'a> {
+	/// Counts events in the attached process only.
+	Process(pmc::Counter<'a>),
+	/// Counts events in the attached process and every process it starts.
+	Descendants(Descendants),
 }
 
 impl<'a> PmcEvent<'a> {
@@ -33,12 +46,30 @@ impl<'a> PmcEvent<'a> {
 
 		Ok(PmcEvent {
 			spec,
-			counter,
+			counter: Pmc::Process(counter),
 			alias: None,
 			value: None,
 		})
 	}
 
+	/// Count events in all child processes created by the process the counter
+	/// is attached to, as well as the process itself (`PMC_F_DESCENDANTS`).
+	///
+	/// Unlike perf events, hwpmc tracks descendant processes rather than
+	/// threads - the threads of a process are always counted.
+	pub fn inherit(mut self, inherit: bool) -> Result<Self, Error> {
+		self.counter = match (inherit, self.counter) {
+			(true, Pmc::Process(_)) => Pmc::Descendants(Descendants::new(self.spec)?),
+			(false, Pmc::Descendants(_)) => Pmc::Process(pmc::Counter::new(
+				self.spec,
+				&pmc::Scope::Process,
+				pmc::CPU_ANY,
+			)?),
+			(_, counter) => counter,
+		};
+		Ok(self)
+	}
+
 	/// Set an alternative (human friendly) name for the configured event,
 	/// displayed when printing the counter value instead of the raw event name.
 	pub fn alias(mut self, alias: &'a str) -> Self {
@@ -49,7 +80,10 @@ impl<'a> PmcEvent<'a> {
 
 impl<'a> Counter for PmcEvent<'a> {
 	fn attach(&mut self, pid: u32) -> Result<(), Error> {
-		self.counter.attach(pid).map_err(Error::PmcError)?;
+		match self.counter {
+			Pmc::Process(ref mut c) => c.attach(pid).map_err(Error::PmcError)?,
+			Pmc::Descendants(ref mut d) => d.attach(pid)?,
+		}
 
 		// Another hwpmc quirk? This process has to allocate and run a PMC after
 		// attaching PMCs to the child, otherwise the PMCs attached to the child
@@ -71,24 +105,100 @@ impl<'a> Counter for PmcEvent<'a> {
 	}
 
 	fn start(&mut self) -> Result<(), Error> {
-		self.counter.start().map_err(Error::PmcError)
+		match self.counter {
+			Pmc::Process(ref mut c) => c.start().map_err(Error::PmcError),
+			Pmc::Descendants(ref mut d) => d.start(),
+		}
+	}
+
+	fn stop(&mut self) -> Result<(), Error> {
+		match self.counter {
+			Pmc::Process(ref mut c) => c.stop().map_err(Error::PmcError),
+			Pmc::Descendants(ref mut d) => d.stop(),
+		}
+	}
+
+	fn set(&mut self, value: u64) -> Result<u64, Error> {
+		let v = match self.counter {
+			Pmc::Process(ref mut c) => c.set(value).map_err(Error::PmcError)?,
+			Pmc::Descendants(ref mut d) => d.set(value)?,
+		};
+		self.value = Some(v);
+		Ok(v)
+	}
+}
+
+/// `Descendants` is a process-scoped PMC allocated with `PMC_F_DESCENDANTS`,
+/// which pmc-rs doesn't expose, so libpmc is called directly.
+///
+/// libpmc must already be initialised (by allocating a [`pmc::Counter`]).
+///
+/// [`pmc::Counter`]: https://itsallbroken.com/code/docs/pmc-rs/pmc/struct.Counter.html
+#[derive(Debug)]
+struct Descendants {
+	id: pmc_sys::pmc_id_t,
+	running: bool,
+}
+
+impl Descendants {
+	fn new(spec: &str) -> Result<Self, Error> {
+		let spec = CString::new(spec)?;
+
+		let mut id = 0;
+		check(unsafe {
+			pmc_sys::pmc_allocate(
+				spec.as_ptr(),
+				pmc_sys::pmc_mode_PMC_MODE_TC,
+				pmc_sys::PMC_F_DESCENDANTS,
+				pmc::CPU_ANY,
+				&mut id,
+			)
+		})?;
+
+		Ok(Descendants { id, running: false })
+	}
+
+	fn attach(&mut self, pid: u32) -> Result<(), Error> {
+		check(unsafe { pmc_sys::pmc_attach(self.id, pid as pmc_sys::pid_t) })
+	}
+
+	fn start(&mut self) -> Result<(), Error> {
+		check(unsafe { pmc_sys::pmc_start(self.id) })?;
+		self.running = true;
+		Ok(())
 	}
 
 	fn stop(&mut self) -> Result<(), Error> {
-		self.counter.stop().map_err(Error::PmcError)
+		check(unsafe { pmc_sys::pmc_stop(self.id) })?;
+		self.running = false;
+		Ok(())
 	}
 
+	/// Read the counter value and set it to value.
 	fn set(&mut self, value: u64) -> Result<u64, Error> {
-		self.counter
-			.set(value)
-			.and_then(|v| {
-				self.value = Some(v);
-				Ok(v)
-			})
-			.map_err(Error::PmcError)
+		let mut old = 0;
+		check(unsafe { pmc_sys::pmc_rw(self.id, value, &mut old) })?;
+		Ok(old)
+	}
+}
+
+impl Drop for Descendants {
+	fn drop(&mut self) {
+		if self.running {
+			let _ = self.stop();
+		}
+		unsafe { pmc_sys::pmc_release(self.id) };
 	}
 }
 
+/// Returns the errno of a failed libpmc call.
+fn check(ret: libc::c_int) -> Result<(), Error> {
+	if ret != 0 {
+		return Err(Error::HwpmcError(Errno::last()));
+	}
+	Ok(())
+}
+
 impl<'a> fmt::Display for PmcEvent<'a> {
 	/// Prints the counter name (or alias) and value in the format:
 	///
@@ -110,6 +220,9 @@ impl<'a> Printable for PmcEvent<'a> {
 	fn name(&self) -> &str {
 		self.alias.unwrap_or(self.spec)
 	}
+	fn spec(&self) -> &str {
+		self.spec
+	}
 	fn value(&self) -> u64 {
 		self.value.unwrap_or(0)
 	}
@@ -137,6 +250,30 @@ mod tests {
 		assert_eq!(event.value(), v);
 	}
 
+	#[test]
+	#[ignore]
+	fn test_inherit() {
+		let mut event = PmcEvent::new("instructions")
+			.unwrap()
+			.inherit(true)
+			.unwrap();
+
+		// Count a shell and the process it starts
+		let mut child = std::process::Command::new("sh")
+			.args(&["-c", "read x; /usr/bin/true; exit 0"])
+			.stdin(std::process::Stdio::piped())
+			.spawn()
+			.unwrap();
+
+		assert!(event.attach(child.id()).is_ok());
+		assert!(event.start().is_ok());
+		drop(child.stdin.take());
+		assert!(child.wait().unwrap().success());
+		assert!(event.stop().is_ok());
+
+		assert!(event.set(0).unwrap() > 0);
+	}
+
 	#[test]
 	#[ignore]
 	fn test_alias() {
@@ -1,5 +1,6 @@
+use baseline::Baseline;
 use error::Error;
-use event::Printable;
+use event::{Breakdown, Group, Printable, Relative, Sample, Sampled};
 use runner::Counter;
 use std::fmt::{self, Display};
 
@@ -13,14 +14,14 @@ use std::fmt::{self, Display};
 /// ```
 ///
 #[allow(dead_code)]
-pub struct RelativePrinter<T: Printable + Counter + Display> {
+pub struct RelativePrinter<T: Printable + Counter + Display + Sampled> {
 	absolute: T,
 	relatives: Vec<T>,
 }
 
 impl<T> fmt::Display for RelativePrinter<T>
 where
-	T: Printable + Counter + Display,
+	T: Printable + Counter + Display + Sampled,
 {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		self.absolute.fmt(f)?;
@@ -28,8 +29,12 @@ where
 
 		for r in &self.relatives {
 			r.fmt(f)?;
-			let rel = (r.value() as f64 / self.absolute.value() as f64) * f64::from(100);
-			writeln!(f, "    ({: >5.1}% of {})", rel, self.absolute.name(),)?;
+			writeln!(
+				f,
+				"    ({: >5.1}% of {})",
+				self.relative(r),
+				self.absolute.name(),
+			)?;
 		}
 
 		Ok(())
@@ -38,7 +43,7 @@ where
 
 impl<T> Counter for RelativePrinter<T>
 where
-	T: Printable + Counter + Display,
+	T: Printable + Counter + Display + Sampled,
 {
 	fn attach(&mut self, pid: u32) -> Result<(), Error> {
 		self.absolute.attach(pid)?;
@@ -70,9 +75,57 @@ where
 	}
 }
 
+impl<T> Sampled for RelativePrinter<T>
+where
+	T: Printable + Counter + Display + Sampled,
+{
+	fn samples(&self) -> Vec<Sample<'_>> {
+		let mut samples = self.absolute.samples();
+		for r in &self.relatives {
+			samples.extend(r.samples().into_iter().map(|mut s| {
+				s.relative = Some(Relative {
+					to: self.absolute.name(),
+					percent: self.relative(r),
+				});
+				s
+			}));
+		}
+		samples
+	}
+
+	fn compare(&mut self, baseline: &Baseline) {
+		self.absolute.compare(baseline);
+		for c in &mut self.relatives {
+			c.compare(baseline);
+		}
+	}
+
+	fn exclude(&mut self, run: usize) {
+		self.absolute.exclude(run);
+		for c in &mut self.relatives {
+			c.exclude(run);
+		}
+	}
+
+	fn subtract(&mut self, run: usize, event: &str, value: u64) {
+		self.absolute.subtract(run, event, value);
+		for c in &mut self.relatives {
+			c.subtract(run, event, value);
+		}
+	}
+
+	fn breakdown(&self, group: Group) -> Vec<Breakdown<'_>> {
+		let mut breakdown = self.absolute.breakdown(group);
+		for r in &self.relatives {
+			breakdown.extend(r.breakdown(group));
+		}
+		breakdown
+	}
+}
+
 impl<T> RelativePrinter<T>
 where
-	T: Printable + Counter + Display,
+	T: Printable + Counter + Display + Sampled,
 {
 	#[allow(dead_code)]
 	pub fn new(absolute: T, relatives: Vec<T>) -> Self {
@@ -81,4 +134,9 @@ where
 			relatives,
 		}
 	}
+
+	/// relative returns the value of r as a percentage of the absolute value.
+	fn relative(&self, r: &T) -> f64 {
+		(r.value() as f64 / self.absolute.value() as f64) * f64::from(100)
+	}
 }
@@ -1,6 +1,8 @@
+use baseline::Baseline;
 use error::Error;
-use event::Printable;
+use event::{self, Breakdown, Group, Printable, Sample, Sampled, Task};
 use runner::Counter;
+use stats::{self, Center, Spread};
 use std::fmt::{self, Display};
 
 use separator::Separatable;
@@ -15,12 +17,37 @@ use separator::Separatable;
 ///                unhalted-cycles:  7,002,094,130 ±4.2%
 /// ```
 ///
+/// The mean and RSD are printed by default, though any [`Center`] and
+/// [`Spread`] can be configured instead - for example, the median and the
+/// median absolute deviation are far less sensitive to a single outlier run.
+///
+/// When compared against a [`Baseline`], the difference between the value and
+/// the same measure of the baseline values is appended, along with the
+/// significance of the difference (see [`Comparison`]):
+///
+/// ```text
+///                unhalted-cycles:  7,002,094,130 ±4.2%     +84,025,129 (+1.2%)   p=0.021 u=0.034 [+0.2%, +2.2%]    *
+/// ```
+///
+/// [`Baseline`]: ../../baseline/struct.Baseline.html
+/// [`Comparison`]: ../../stats/struct.Comparison.html
+/// [`Center`]: ../../stats/enum.Center.html
+/// [`Spread`]: ../../stats/enum.Spread.html
 /// [relative standard deviation]: https://en.wikipedia.org/wiki/Coefficient_of_variation  
 /// [set]: #method.set
 ///
 pub struct RSDPrinter<T: Counter + Printable + Display> {
 	counter: T,
 	values: Vec<u64>,
+	/// The values of each process and thread of the target, per run (empty
+	/// unless recorded by the counter).
+	tasks: Vec<Vec<Task>>,
+	center: Center,
+	spread: Spread,
+
+	/// Values recorded for this event in a baseline - empty if the baseline has
+	/// no record of this event.
+	baseline: Option<Vec<u64>>,
 }
 
 impl<T> Counter for RSDPrinter<T>
@@ -39,9 +66,9 @@ where
 	fn set(&mut self, value: u64) -> Result<u64, Error> {
 		// TODO: cache computed stats values and reset here?
 
-		self.counter.set(value).and_then(|v| {
+		self.counter.set(value).inspect(|&v| {
 			self.values.push(v);
-			Ok(v)
+			self.tasks.push(self.counter.tasks().to_vec());
 		})
 	}
 }
@@ -51,8 +78,13 @@ where
 	T: Counter + Printable + Display,
 {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		let rsd = if self.values.len() > 1 {
-			format!("±{:<5}", format!("{:.1}%", self.rsd()))
+		let spread = if self.values.len() > 1 {
+			let v = self.spread.of(&self.values);
+			if self.spread.is_relative() {
+				format!("±{:<5}", format!("{:.1}%", v))
+			} else {
+				format!("±{:<5}", (v.round() as u64).separated_string())
+			}
 		} else {
 			String::from("      ")
 		};
@@ -62,8 +94,32 @@ where
 			"{:>30}: {:>14} {}",
 			self.counter.name(),
 			self.value().separated_string(),
-			rsd,
-		)
+			spread,
+		)?;
+
+		match self.baseline {
+			Some(ref b) if !b.is_empty() => {
+				let base = self.center.of(b);
+				let delta = self.center.of(&self.values) - base;
+				let pct = if base == 0.0 {
+					String::from("")
+				} else {
+					format!("({:+.1}%)", delta * 100.0 / base)
+				};
+
+				let sign = if delta < 0.0 { "-" } else { "+" };
+				let delta = format!("{}{}", sign, (delta.abs().round() as u64).separated_string());
+
+				write!(f, "   {:>14} {:<9}", delta, pct)?;
+
+				match stats::compare(b, &self.values) {
+					Some(c) => write!(f, " {}", c),
+					None => Ok(()),
+				}
+			}
+			Some(_) => write!(f, "   {:>14} {:<9}", "-", ""),
+			None => Ok(()),
+		}
 	}
 }
 
@@ -75,63 +131,88 @@ where
 		self.counter.name()
 	}
 
+	fn spec(&self) -> &str {
+		self.counter.spec()
+	}
+
 	fn value(&self) -> u64 {
-		self.mean()
+		self.center.of(&self.values) as u64
 	}
 }
 
-impl<T> RSDPrinter<T>
+impl<T> Sampled for RSDPrinter<T>
 where
 	T: Counter + Printable + Display,
 {
-	#[allow(dead_code)]
-	pub fn new(counter: T) -> Self {
-		RSDPrinter {
-			counter,
-			values: Vec::new(),
-		}
+	fn samples(&self) -> Vec<Sample<'_>> {
+		vec![Sample {
+			name: self.counter.name(),
+			spec: self.counter.spec(),
+			values: &self.values,
+			relative: None,
+		}]
 	}
 
-	/// rsd returns the relative standard deviation of the observed counter values.
-	pub fn rsd(&self) -> f64 {
-		if self.values.len() < 2 {
-			// Don't panic on division of 0
-			return 0.0;
+	fn compare(&mut self, baseline: &Baseline) {
+		let values = baseline.get(self.counter.name()).unwrap_or(&[]);
+		self.baseline = Some(values.to_vec());
+	}
+
+	fn exclude(&mut self, run: usize) {
+		if run < self.values.len() {
+			self.values.remove(run);
+			self.tasks.remove(run);
 		}
+	}
 
-		(self.stddev() * f64::from(100)) / self.mean() as f64
+	fn subtract(&mut self, run: usize, event: &str, value: u64) {
+		if self.counter.name() != event {
+			return;
+		}
+		if let Some(v) = self.values.get_mut(run) {
+			*v = v.saturating_sub(value);
+		}
 	}
 
-	/// mean returns the arithmetic mean of the observed counter values.
-	fn mean(&self) -> u64 {
-		if self.values.is_empty() {
-			return 0;
+	fn breakdown(&self, group: Group) -> Vec<Breakdown<'_>> {
+		if self.tasks.iter().all(Vec::is_empty) {
+			return vec![];
 		}
 
-		self.values.iter().sum::<u64>() / self.values.len() as u64
+		vec![Breakdown {
+			name: self.counter.name(),
+			tasks: event::breakdown(&self.tasks, group),
+		}]
 	}
+}
 
-	/// variance returns the variance of the observed counter values.
-	fn variance(&self) -> f64 {
-		if self.values.len() < 2 {
-			// Don't panic on division of (len - 1) below
-			return 0.0;
+impl<T> RSDPrinter<T>
+where
+	T: Counter + Printable + Display,
+{
+	#[allow(dead_code)]
+	pub fn new(counter: T) -> Self {
+		RSDPrinter {
+			counter,
+			values: Vec::new(),
+			tasks: Vec::new(),
+			center: Center::Mean,
+			spread: Spread::Rsd,
+			baseline: None,
 		}
+	}
 
-		let mean = self.mean() as f64;
-		let total = self.values.iter().fold(0.0, |acc, v| {
-			let v = *v as f64;
-			let x = v - mean;
-			acc + x * x
-		});
-
-		let divisor = (self.values.len() - 1) as f64;
-		total / divisor
+	/// Set the measure of central tendency displayed (and used as the value of
+	/// this counter).
+	pub fn center(mut self, center: Center) -> Self {
+		self.center = center;
+		self
 	}
 
-	/// stddev returns the standard deviation of the observed counter values.
-	fn stddev(&self) -> f64 {
-		self.variance().sqrt()
+	/// Set the measure of dispersion displayed.
+	pub fn spread(mut self, spread: Spread) -> Self {
+		self.spread = spread;
+		self
 	}
 }
 
@@ -143,6 +224,7 @@ mod tests {
 	use self::mock_event::MockEvent;
 
 	#[test]
+	#[allow(clippy::excessive_precision)]
 	fn stats() {
 		let mut values = vec![0, 10, 20, 30, 40];
 		let mut p = RSDPrinter::new(MockEvent::new("mock", &values.clone()));
@@ -159,11 +241,98 @@ mod tests {
 		assert_eq!(p.value(), 20);
 
 		// Variance
-		assert_eq!(p.variance(), 250.0);
-		assert_eq!(p.stddev() as f32, 15.811388);
+		assert_eq!(stats::variance(&p.values), 250.0);
+		assert_eq!(stats::stddev(&p.values) as f32, 15.811388);
 
 		// RSD
-		assert_eq!(p.rsd() as f32, 79.0569415);
+		assert_eq!(stats::rsd(&p.values) as f32, 79.0569415);
+	}
+
+	#[test]
+	fn compare() {
+		let values = vec![10, 20];
+		let mut p = RSDPrinter::new(MockEvent::new("mock", &values));
+		for _ in values.iter() {
+			p.set(0).unwrap();
+		}
+
+		let dir = std::env::temp_dir().join(format!("benchpmc-rsd-{}", std::process::id()));
+		let saved = vec![Sample {
+			name: "mock",
+			spec: "mock",
+			values: &[10, 10],
+			relative: None,
+		}];
+		Baseline::save(&dir, "base", &saved).unwrap();
+		let baseline = Baseline::load(&dir, "base").unwrap();
+		std::fs::remove_dir_all(&dir).unwrap();
+
+		assert_eq!(format!("{}", p), format!("{:>30}: {:>14} ±47.1%", "mock", 15));
+
+		p.compare(&baseline);
+		assert_eq!(p.baseline, Some(vec![10, 10]));
+		assert!(format!("{}", p).contains("             +5 (+50.0%) "));
+		assert!(format!("{}", p).ends_with(" p=0.500 u=0.617 [-585.3%, +685.3%]  "));
+	}
+
+	#[test]
+	fn exclude() {
+		let values = vec![30, 20, 10];
+		let mut p = RSDPrinter::new(MockEvent::new("mock", &values));
+		for _ in values.iter() {
+			p.set(0).unwrap();
+		}
+
+		p.exclude(1);
+		assert_eq!(p.values, vec![10, 30]);
+
+		// Out of range runs are ignored
+		p.exclude(5);
+		assert_eq!(p.values, vec![10, 30]);
+	}
+
+	#[test]
+	fn subtract() {
+		let values = vec![30, 20, 10];
+		let mut p = RSDPrinter::new(MockEvent::new("mock", &values));
+		for _ in values.iter() {
+			p.set(0).unwrap();
+		}
+
+		p.subtract(1, "mock", 15);
+		assert_eq!(p.values, vec![10, 5, 30]);
+
+		// Saturates at zero
+		p.subtract(0, "mock", 15);
+		assert_eq!(p.values, vec![0, 5, 30]);
+
+		// Other events and out of range runs are ignored
+		p.subtract(2, "other", 15);
+		p.subtract(5, "mock", 15);
+		assert_eq!(p.values, vec![0, 5, 30]);
+	}
+
+	#[test]
+	fn center_spread() {
+		let values = vec![100, 101, 99, 100, 100_000];
+		let mut p = RSDPrinter::new(MockEvent::new("mock", &values))
+			.center(Center::Median)
+			.spread(Spread::Mad);
+		for _ in values.iter() {
+			p.set(0).unwrap();
+		}
+
+		assert_eq!(p.value(), 100);
+		assert_eq!(format!("{}", p), format!("{:>30}: {:>14} ±1    ", "mock", 100));
+
+		let p = p.spread(Spread::Rmad);
+		assert_eq!(format!("{}", p), format!("{:>30}: {:>14} ±1.0% ", "mock", 100));
+
+		let p = p.spread(Spread::Stddev);
+		assert_eq!(
+			format!("{}", p),
+			format!("{:>30}: {:>14} ±44,677", "mock", 100)
+		);
 	}
 
 	#[test]
@@ -172,9 +341,21 @@ mod tests {
 		let p = RSDPrinter::new(MockEvent::new("mock", &values));
 
 		assert_eq!(p.value(), 0);
-		assert_eq!(p.variance(), 0.0);
-		assert_eq!(p.stddev(), 0.0);
-		assert_eq!(p.rsd(), 0.0);
+		assert_eq!(stats::variance(&p.values), 0.0);
+		assert_eq!(stats::stddev(&p.values), 0.0);
+		assert_eq!(stats::rsd(&p.values), 0.0);
+	}
+
+	#[test]
+	fn div_zero_mean() {
+		let values = vec![0, 0, 0];
+		let mut p = RSDPrinter::new(MockEvent::new("mock", &values));
+		for _ in values.iter() {
+			p.set(0).unwrap();
+		}
+
+		assert_eq!(p.value(), 0);
+		assert_eq!(stats::rsd(&p.values), 0.0);
 	}
 
 	#[test]
@@ -183,8 +364,8 @@ mod tests {
 		let p = RSDPrinter::new(MockEvent::new("mock", &values));
 
 		assert_eq!(p.value(), 0);
-		assert_eq!(p.variance(), 0.0);
-		assert_eq!(p.stddev(), 0.0);
-		assert_eq!(p.rsd(), 0.0);
+		assert_eq!(stats::variance(&p.values), 0.0);
+		assert_eq!(stats::stddev(&p.values), 0.0);
+		assert_eq!(stats::rsd(&p.values), 0.0);
 	}
 }
@@ -38,6 +38,9 @@ impl<'a> Printable for MockEvent<'a> {
 	fn name(&self) -> &str {
 		self.name
 	}
+	fn spec(&self) -> &str {
+		self.name
+	}
 	fn value(&self) -> u64 {
 		self.value.unwrap()
 	}
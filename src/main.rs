@@ -6,29 +6,100 @@ extern crate nix;
 extern crate pmc;
 extern crate separator;
 
+mod baseline;
+mod cpulist;
+mod duration;
 mod error;
 mod event;
+mod report;
 mod runner;
+mod session;
+mod stats;
 
-#[cfg(all(debug_assertions, not(target_os = "freebsd")))]
+#[cfg(all(
+    debug_assertions,
+    not(any(target_os = "freebsd", target_os = "linux"))
+))]
 use event::MockEvent;
+#[cfg(target_os = "linux")]
+use event::PerfEvent;
 #[cfg(target_os = "freebsd")]
-use event::{PmcEvent, RSDPrinter, RelativePrinter};
+use event::PmcEvent;
+#[cfg(any(target_os = "freebsd", target_os = "linux"))]
+use event::{RSDPrinter, RelativePrinter};
+#[cfg(any(target_os = "freebsd", target_os = "linux"))]
+use stats::{Center, Spread};
 
 use ansi_term::Colour::Yellow;
+use baseline::{Baseline, BASELINE_DIR};
 use clap::{App, AppSettings, Arg};
-use runner::Counter;
-use std::fmt::Display;
+use event::Group;
+use report::Report;
+use runner::{Input, Options, Output, SHELL};
+use separator::Separatable;
+use session::{DisplayCounter, Hooks, Policy, Session};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process;
 use std::time::Instant;
 
-/// `DisplayCounter` composes the traits required to both run, and display a
-/// counter
-trait DisplayCounter: Counter + Display {}
-impl<T: Counter + Display> DisplayCounter for T {}
+/// `CALIBRATION_RUNS` is the number of times the overhead of the shell is
+/// measured for `--shell-overhead`, or of `NULL_TARGET` for `--calibrate`.
+const CALIBRATION_RUNS: usize = 10;
+
+/// `NULL_TARGET` is executed to measure the fixed cost of executing any target
+/// (exec, dynamic linking and exit) for `--calibrate`.
+const NULL_TARGET: &str = "true";
+
+#[cfg(not(target_os = "linux"))]
+const AFTER_HELP: &str = "\
+Uses the libpmc userland interface for hpwmc to measure performance counters on 
+supported CPUs - see hwpmc(4) for supported devices. Your kernel must have been 
+compiled with hwpmc support, or the module loaded at runtime.
+
+Event specifiers are passed through to libpmc unchanged, therefore any valid event 
+specifier can be used (such as UOPS_RETIRED.ALL), including event qualifiers (such 
+as setting the cmask, or filtering events by CPU privilege level).
+
+See the pmc manpage for your CPU type for more information (i.e. pmc.haswell(3) 
+for the Intel Haswell microarchitecture) - try running 'apropos pmc.'
+
+If count is > 1, the average value is printed along with the relative standard 
+deviation for observed counter values. Only per-process events are supported.
+
+With --shell or --inherit, events in every process started by the target are 
+summed by hwpmc. The value of each process or thread cannot be read, so 
+--breakdown is only available on Linux.";
+
+#[cfg(target_os = "linux")]
+const AFTER_HELP: &str = "\
+Uses the perf_event_open(2) interface to measure performance counters. Access to 
+counters is governed by /proc/sys/kernel/perf_event_paranoid - see perf_event_open(2).
+
+Event specifiers are the generalised event names listed by perf-list(1) (such as 
+instructions, branch-misses or task-clock), or raw hex event codes (such as r01c2), 
+optionally suffixed with a :u or :k modifier to only count user or kernel mode.
+
+Software events (task-clock, context-switches, page-faults, cpu-migrations) are 
+provided by the kernel and are available even without a hardware PMU (i.e. in VMs).
+
+If count is > 1, the average value is printed along with the relative standard 
+deviation for observed counter values. Only per-process events are supported.";
+
+/// Print a progress message to stdout, or to stderr when stdout is reserved for
+/// a machine-readable report.
+macro_rules! progress {
+    ($machine:expr, $($arg:tt)*) => {
+        if $machine {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
 
 fn main() {
-    let matchers = App::new("benchpmc")
+    let app = App::new("benchpmc")
         .setting(AppSettings::AllowLeadingHyphen)
         .author(crate_authors!())
         .version(crate_version!())
@@ -39,7 +110,7 @@ fn main() {
             Arg::with_name("target")
                 .help("Executable to profile")
                 .index(1)
-                .required(true),
+                .required_unless_one(&["compare", "pid"]),
         )
         .arg(
             Arg::with_name("event-spec")
@@ -58,107 +129,1154 @@ fn main() {
                 .default_value("10")
                 .help("Number of times to measure target"),
         )
-        // TODO: write samples to an outdir for further processing / graphing
-        //
-        // .arg(
-        //     Arg::with_name("outdir")
-        //         .short("o")
-        //         .long("output")
-        //         .takes_value(true)
-        //         .multiple(false)
-        //         .help("Output directory to write raw measurement values"),
-        // )
+        .arg(
+            Arg::with_name("target-rsd")
+                .long("target-rsd")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("PERCENT")
+                .help("Measure target until the RSD of the first event (i.e. instructions) is below PERCENT (ignores --count)"),
+        )
+        .arg(
+            Arg::with_name("rsd-event")
+                .long("rsd-event")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("EVENT")
+                .requires("target-rsd")
+                .help("Consider the RSD of EVENT for --target-rsd instead of the first event"),
+        )
+        .arg(
+            Arg::with_name("min-runs")
+                .long("min-runs")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("N")
+                .requires("target-rsd")
+                .help("Minimum number of times to measure target for --target-rsd [default: 5]"),
+        )
+        .arg(
+            Arg::with_name("max-runs")
+                .long("max-runs")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("N")
+                .requires("target-rsd")
+                .help("Maximum number of times to measure target for --target-rsd [default: 100]"),
+        )
+        .arg(
+            Arg::with_name("max-time")
+                .long("max-time")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("DURATION")
+                .requires("target-rsd")
+                .help("Stop measuring target for --target-rsd after DURATION (i.e. 30s)"),
+        )
+        .arg(
+            Arg::with_name("warmup")
+                .long("warmup")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("N")
+                .default_value("0")
+                .help("Number of times to run target before measuring, discarding the results"),
+        )
+        .arg(
+            Arg::with_name("center")
+                .long("center")
+                .takes_value(true)
+                .multiple(false)
+                .possible_values(&["mean", "median", "min", "max", "p5", "p95"])
+                .default_value("mean")
+                .help("Central tendency of the observed values to display"),
+        )
+        .arg(
+            Arg::with_name("spread")
+                .long("spread")
+                .takes_value(true)
+                .multiple(false)
+                .possible_values(&["rsd", "rmad", "stddev", "mad"])
+                .default_value("rsd")
+                .help("Dispersion of the observed values to display"),
+        )
+        .arg(
+            Arg::with_name("reject-outliers")
+                .long("reject-outliers")
+                .help("Discard runs with an outlier value for any event"),
+        )
+        .arg(
+            Arg::with_name("on-failure")
+                .long("on-failure")
+                .takes_value(true)
+                .multiple(false)
+                .possible_values(&["abort", "ignore", "exclude"])
+                .default_value("abort")
+                .help("Action taken when target exits with a non-zero status or is killed by a signal"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("DURATION")
+                .help("Terminate target if it runs for longer than DURATION (i.e. 500ms, 10s)"),
+        )
+        .arg(
+            Arg::with_name("timeout-signal")
+                .long("timeout-signal")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("SIGNAL")
+                .default_value("TERM")
+                .help("Signal sent to the process group of target on timeout, followed by KILL if it keeps running"),
+        )
+        .arg(
+            Arg::with_name("on-timeout")
+                .long("on-timeout")
+                .takes_value(true)
+                .multiple(false)
+                .possible_values(&["abort", "ignore", "exclude"])
+                .default_value("abort")
+                .help("Action taken when target is terminated on timeout"),
+        )
+        .arg(
+            Arg::with_name("target-output")
+                .long("target-output")
+                .takes_value(true)
+                .multiple(false)
+                .possible_values(&["inherit", "null", "file", "capture"])
+                .default_value("inherit")
+                .help("Destination of target stdout/stderr: discarded, written to a file per run in the output directory, or captured and shown if a run fails"),
+        )
+        .arg(
+            Arg::with_name("stdin")
+                .long("stdin")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("FILE")
+                .help("Read the stdin of target from FILE (or - to read the stdin of benchpmc once), from the start on every run"),
+        )
+        .arg(
+            Arg::with_name("env")
+                .long("env")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("KEY=VAL")
+                .help("Set an environment variable of target"),
+        )
+        .arg(
+            Arg::with_name("env-clear")
+                .long("env-clear")
+                .help("Run target with an empty environment, other than variables set with --env"),
+        )
+        .arg(
+            Arg::with_name("cwd")
+                .long("cwd")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("DIR")
+                .help("Run target in DIR (a relative target path is resolved from DIR)"),
+        )
+        .arg(
+            Arg::with_name("cpu")
+                .long("cpu")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("LIST")
+                .help("Run target on the CPUs in LIST only (i.e. 2 or 0-3,6)"),
+        )
+        .arg(
+            Arg::with_name("nice")
+                .long("nice")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("N")
+                .allow_hyphen_values(true)
+                .help("Run target with a nice value of N"),
+        )
+        .arg(
+            Arg::with_name("rtprio")
+                .long("rtprio")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("N")
+                .help("Run target with a realtime scheduling priority of N"),
+        )
+        .arg(
+            Arg::with_name("no-aslr")
+                .long("no-aslr")
+                .help("Run target with address space layout randomisation disabled"),
+        )
+        .arg(
+            Arg::with_name("random-env")
+                .long("random-env")
+                .help("Pad the environment of target by a random amount every run to randomise stack alignment"),
+        )
+        .arg(
+            Arg::with_name("markers")
+                .long("markers")
+                .conflicts_with("pid")
+                .help("Only count events between the start and stop markers sent by target (see BENCHPMC_CONTROL_FD)"),
+        )
+        .arg(
+            Arg::with_name("setup")
+                .long("setup")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("CMD")
+                .help("Shell command run once before the first run of target"),
+        )
+        .arg(
+            Arg::with_name("prepare")
+                .long("prepare")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("CMD")
+                .help("Shell command run before every run of target, unmeasured"),
+        )
+        .arg(
+            Arg::with_name("conclude")
+                .long("conclude")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("CMD")
+                .help("Shell command run after every run of target, unmeasured"),
+        )
+        .arg(
+            Arg::with_name("cleanup")
+                .long("cleanup")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("CMD")
+                .help("Shell command run once after the last run of target"),
+        )
+        .arg(
+            Arg::with_name("outdir")
+                .long("output")
+                .takes_value(true)
+                .multiple(false)
+                .help("Output directory to write raw measurement values"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .multiple(false)
+                .possible_values(&["text", "json", "csv"])
+                .default_value("text")
+                .help("Output format of the results"),
+        )
+        .arg(
+            Arg::with_name("separator")
+                .long("field-separator")
+                .takes_value(true)
+                .multiple(false)
+                .default_value(",")
+                .help("Field separator used by the csv output format"),
+        )
+        .arg(
+            Arg::with_name("save-baseline")
+                .long("save-baseline")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("NAME")
+                .help("Save the observed values as a named baseline"),
+        )
+        .arg(
+            Arg::with_name("baseline")
+                .long("baseline")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("NAME")
+                .help("Compare the observed values against a saved baseline"),
+        )
+        .arg(
+            Arg::with_name("compare")
+                .long("compare")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("COMMAND")
+                .conflicts_with_all(&["target", "baseline", "save-baseline"])
+                .help("Compare two or more command lines, run interleaved"),
+        )
+        .arg(
+            Arg::with_name("pid")
+                .long("pid")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("PID")
+                .requires("duration")
+                .conflicts_with_all(&["target", "compare", "shell"])
+                .help("Attach to the running process PID instead of executing target, measuring --count intervals"),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .long("duration")
+                .takes_value(true)
+                .multiple(false)
+                .value_name("DURATION")
+                .requires("pid")
+                .help("Length of each interval measured with --pid (i.e. 500ms, 10s)"),
+        )
+        .arg(
+            Arg::with_name("shell")
+                .long("shell")
+                .help("Run target (and its args) as a command line with /bin/sh -c, counting events in every process it starts"),
+        )
+        .arg(
+            Arg::with_name("shell-overhead")
+                .long("shell-overhead")
+                .requires("shell")
+                .help("Measure the overhead of the shell and subtract it from every event"),
+        )
+        .arg(
+            Arg::with_name("inherit")
+                .long("inherit")
+                .help("Count events in every process and thread started by target"),
+        )
+        .arg(
+            Arg::with_name("calibrate")
+                .long("calibrate")
+                .conflicts_with_all(&["shell-overhead", "markers", "pid"])
+                .help("Measure the overhead of executing a null target and subtract it from every event"),
+        )
         .arg(Arg::with_name("args").takes_value(true).multiple(true))
-        .after_help(
-            "\
-Uses the libpmc userland interface for hpwmc to measure performance counters on 
-supported CPUs - see hwpmc(4) for supported devices. Your kernel must have been 
-compiled with hwpmc support, or the module loaded at runtime.
+        .after_help(AFTER_HELP);
 
-Event specifiers are passed through to libpmc unchanged, therefore any valid event 
-specifier can be used (such as UOPS_RETIRED.ALL), including event qualifiers (such 
-as setting the cmask, or filtering events by CPU privilege level).
-
-See the pmc manpage for your CPU type for more information (i.e. pmc.haswell(3) 
-for the Intel Haswell microarchitecture) - try running 'apropos pmc.'
+    // Reading the value of each process started by the target is only
+    // supported by perf_event_open(2) - hwpmc(4) only sums them
+    #[cfg(target_os = "linux")]
+    let app = app
+        .arg(
+            Arg::with_name("breakdown")
+                .long("breakdown")
+                .takes_value(true)
+                .multiple(false)
+                .possible_values(&["process", "thread"])
+                .help("Display the value of each event per process or thread of target (implies --inherit)"),
+        );
 
-If count is > 1, the average value is printed along with the relative standard 
-deviation for observed counter values. Only per-process events are supported.",
-        )
-        .get_matches();
+    let matchers = app.get_matches();
 
     let run_count = matchers
         .value_of("count")
         .expect("failed to get --count")
-        .parse::<isize>()
+        .parse::<usize>()
         .unwrap_or_else(|_| {
             println!("Failed to parse --count, using default value");
             10
         });
 
-    let mut args = vec![];
-    if let Some(target_args) = matchers.values_of("args") {
-        for arg in target_args {
-            args.push(arg);
+    // With a target RSD the number of runs is adaptive, otherwise it is fixed
+    let target_rsd = matchers.value_of("target-rsd").map(|v| {
+        match v.trim_end_matches('%').parse::<f64>() {
+            Ok(rsd) if rsd > 0.0 => rsd,
+            _ => {
+                println!("invalid --target-rsd '{}'", v);
+                process::exit(-1);
+            }
         }
+    });
+    let rsd_event = matchers.value_of("rsd-event");
+    let max_time = matchers.value_of("max-time").map(|v| {
+        duration::parse(v).unwrap_or_else(|err| {
+            println!("invalid --max-time: {}", err);
+            process::exit(-1);
+        })
+    });
+    let (min_runs, max_runs) = match target_rsd {
+        Some(_) => (
+            value_t!(matchers, "min-runs", usize).unwrap_or_else(|e| match e.kind {
+                clap::ErrorKind::ArgumentNotFound => 5,
+                _ => e.exit(),
+            }),
+            value_t!(matchers, "max-runs", usize).unwrap_or_else(|e| match e.kind {
+                clap::ErrorKind::ArgumentNotFound => 100,
+                _ => e.exit(),
+            }),
+        ),
+        None => (run_count, run_count),
+    };
+    if min_runs > max_runs {
+        println!("--min-runs must not be greater than --max-runs");
+        process::exit(-1);
     }
-    let args = args; // drop mutability
 
-    let target = matchers.value_of("target").unwrap();
+    let warmup_count = value_t!(matchers, "warmup", usize).unwrap_or_else(|e| e.exit());
 
-    let counters = get_counters(&matchers);
-    if let Err(err) = counters {
-        println!("there was a problem with {}", err);
-        process::exit(-1);
+    // Either a single target and its args, or multiple command lines to compare
+    let mut commands = vec![];
+    let shell = matchers.is_present("shell");
+    if let Some(compare) = matchers.values_of("compare") {
+        for cmd in compare {
+            if shell {
+                commands.push(shell_command(cmd));
+                continue;
+            }
+            commands.push(session::split(cmd).unwrap_or_else(|err| {
+                println!("invalid --compare command: {}", err);
+                process::exit(-1);
+            }));
+        }
+
+        if commands.len() < 2 {
+            println!("--compare requires at least two command lines");
+            process::exit(-1);
+        }
+    } else if shell {
+        let mut cmd = vec![matchers.value_of("target").unwrap()];
+        cmd.extend(matchers.values_of("args").into_iter().flatten());
+        commands.push(shell_command(&cmd.join(" ")));
     }
-    let mut counters = counters.unwrap();
+    let commands = commands; // drop mutability
+
+    let format = matchers.value_of("format").unwrap();
+    let machine = format != "text";
+
+    // Load the baseline up front so a typo doesn't waste a benchmark run
+    let baseline = matchers.value_of("baseline").map(|name| {
+        Baseline::load(Path::new(BASELINE_DIR), name).unwrap_or_else(|err| {
+            println!("failed to load baseline '{}': {}", name, err);
+            process::exit(-1);
+        })
+    });
+
+    let on_failure = value_t!(matchers, "on-failure", Policy).unwrap_or_else(|e| e.exit());
+    let on_timeout = value_t!(matchers, "on-timeout", Policy).unwrap_or_else(|e| e.exit());
+    let options = exec_options(&matchers);
+    let hooks = Hooks {
+        setup: matchers.value_of("setup"),
+        prepare: matchers.value_of("prepare"),
+        conclude: matchers.value_of("conclude"),
+        cleanup: matchers.value_of("cleanup"),
+    };
+
+    let new_session = |target, args| match get_counters(&matchers) {
+        Ok(counters) => Session::new(target, args, counters)
+            .options(options.clone())
+            .on_failure(on_failure)
+            .on_timeout(on_timeout)
+            .hooks(hooks),
+        Err(err) => {
+            println!("there was a problem with {}", err);
+            process::exit(-1);
+        }
+    };
+
+    let mut sessions = vec![];
+    if let Some(pid) = matchers.value_of("pid") {
+        let duration = duration::parse(matchers.value_of("duration").unwrap()).unwrap_or_else(|err| {
+            println!("invalid --duration: {}", err);
+            process::exit(-1);
+        });
+        let id = pid.parse::<u32>().ok().filter(|&id| id > 0).unwrap_or_else(|| {
+            println!("invalid --pid '{}'", pid);
+            process::exit(-1);
+        });
+        sessions.push(new_session(pid, vec![]).attach(id, duration));
+    } else if commands.is_empty() {
+        let target = matchers.value_of("target").unwrap();
+        let args = matchers
+            .values_of("args")
+            .map(|args| args.collect())
+            .unwrap_or_default();
+        sessions.push(new_session(target, args));
+    } else {
+        for cmd in &commands {
+            let args = cmd[1..].iter().map(String::as_str).collect();
+            sessions.push(new_session(&cmd[0], args));
+        }
+    }
+    let comparing = sessions.len() > 1;
+
+    // Output of each compared target is written to a subdirectory named after
+    // its label, as with the raw values
+    let outdir = |i| {
+        let mut path = PathBuf::from(matchers.value_of("outdir").unwrap());
+        if comparing {
+            path.push(report::compare::label(i));
+        }
+        path
+    };
+
+    if matchers.value_of("target-output") == Some("file") {
+        if !matchers.is_present("outdir") {
+            println!("--target-output file requires --output");
+            process::exit(-1);
+        }
+        sessions = sessions
+            .into_iter()
+            .enumerate()
+            .map(|(i, s)| s.output_dir(outdir(i).join("output")))
+            .collect();
+    }
+
+    if let Some(event) = rsd_event {
+        if sessions[0].rsd(rsd_event).is_none() {
+            println!("--rsd-event '{}' is not a measured event", event);
+            process::exit(-1);
+        }
+    }
+
+    let run_range = if min_runs == max_runs {
+        max_runs.to_string()
+    } else {
+        format!("{}-{}", min_runs, max_runs)
+    };
 
     let prompt = Yellow.bold().paint("==> ");
-    println!(
-        "{} running {} '{}' with args {:?} ",
-        prompt, run_count, target, args
-    );
+    for (i, s) in sessions.iter().enumerate() {
+        let label = if comparing {
+            format!("{}: ", report::compare::label(i))
+        } else {
+            String::new()
+        };
+        if let Some(duration) = matchers.value_of("duration") {
+            progress!(
+                machine,
+                "{} measuring {} intervals of {} of pid {}",
+                prompt,
+                run_range,
+                duration,
+                s.target()
+            );
+            continue;
+        }
+        progress!(
+            machine,
+            "{} {}running {} '{}' with args {:?} ",
+            prompt,
+            label,
+            run_range,
+            s.target(),
+            s.args()
+        );
+    }
+
+    for idx in 0..sessions.len() {
+        let s = &mut sessions[idx];
+        if let Err(err) = s.setup() {
+            // Only clean up the sessions that have been set up
+            abort(
+                &mut sessions[..=idx],
+                format!("failed to run setup: {}", err),
+            );
+        }
+        if let Some(err) = s.hook_error() {
+            progress!(machine, "{} {}", prompt, err);
+        }
+    }
+
+    // The fixed cost of executing the shell, or of executing any target
+    let calibration = if matchers.is_present("shell-overhead") {
+        Some(("shell", SHELL, &["-c", ""][..]))
+    } else if matchers.is_present("calibrate") {
+        Some(("null target", NULL_TARGET, &[][..]))
+    } else {
+        None
+    };
+    if let Some((what, target, args)) = calibration {
+        for idx in 0..sessions.len() {
+            let s = &mut sessions[idx];
+            if let Err(err) = s.calibrate(target, args, CALIBRATION_RUNS) {
+                abort(&mut sessions, format!("failed to measure {} overhead: {}", what, err));
+            }
+
+            let overhead: Vec<_> = s
+                .overhead()
+                .iter()
+                .map(|(event, v)| format!("{} {}", event, v.separated_string()))
+                .collect();
+            progress!(machine, "{} {} overhead: {}", prompt, what, overhead.join(", "));
+        }
+    }
+
+    for i in 0..warmup_count {
+        for idx in 0..sessions.len() {
+            let s = &mut sessions[idx];
+            let diff = match s.warmup() {
+                Ok(diff) => diff,
+                Err(err) => {
+                    print_output(s);
+                    abort(&mut sessions, format!("failed to run warmup: {}", err));
+                }
+            };
+            let ms = (diff.as_secs() * 1000) + u64::from(diff.subsec_millis());
+
+            let progress = Yellow.paint(format!("[warmup {}/{}]", i + 1, warmup_count));
+            if comparing {
+                let label = report::compare::label(idx);
+                progress!(machine, "{}{} {}\truntime: {}ms", prompt, progress, label, ms);
+            } else {
+                progress!(machine, "{}{}\truntime: {}ms", prompt, progress, ms);
+            }
+        }
+    }
+
+    // Targets being compared are run interleaved, so any drift over the course
+    // of the benchmark (thermal throttling, background load, etc) affects them
+    // all equally.
+    let started = Instant::now();
+    let mut run_count = 0;
+    while run_count < max_runs {
+        for idx in 0..sessions.len() {
+            let s = &mut sessions[idx];
+            let (diff, mut status) = match s.run() {
+                Ok(run) if run.status.success() && !run.excluded => (run.runtime, String::new()),
+                Ok(run) if run.status.success() => (run.runtime, String::from(" (excluded)")),
+                Ok(run) => {
+                    let excluded = if run.excluded { " (excluded)" } else { "" };
+                    (run.runtime, format!("\t{}{}", run.status, excluded))
+                }
+                Err(err) => {
+                    print_output(s);
+                    abort(&mut sessions, format!("failed to run benchmark: {}", err));
+                }
+            };
+            if let Some(err) = s.hook_error() {
+                status = format!("\t{}{}", err, status);
+            }
+            let ms = (diff.as_secs() * 1000) + u64::from(diff.subsec_millis());
+
+            let progress = Yellow.paint(format!("[{}/{}]", run_count + 1, max_runs));
+            if comparing {
+                let label = report::compare::label(idx);
+                progress!(machine, "{}{} {}\truntime: {}ms{}", prompt, progress, label, ms, status);
+            } else {
+                progress!(machine, "{}{}\truntime: {}ms{}", prompt, progress, ms, status);
+            }
+            print_output(s);
+        }
+        run_count += 1;
+
+        let target = match target_rsd {
+            Some(target) if run_count >= min_runs => target,
+            _ => continue,
+        };
+
+        if sessions.iter().all(|s| s.rsd(rsd_event).unwrap_or(0.0) <= target) {
+            progress!(
+                machine,
+                "{} target RSD of {}% reached after {} runs",
+                prompt,
+                target,
+                run_count
+            );
+            break;
+        }
+
+        if max_time.is_some_and(|t| started.elapsed() >= t) {
+            progress!(
+                machine,
+                "{} target RSD of {}% not reached within --max-time after {} runs",
+                prompt,
+                target,
+                run_count
+            );
+            break;
+        }
+
+        if run_count == max_runs {
+            progress!(
+                machine,
+                "{} target RSD of {}% not reached after --max-runs {} runs",
+                prompt,
+                target,
+                run_count
+            );
+        }
+    }
+
+    for i in 0..sessions.len() {
+        // Clean up the remaining sessions before exiting if one fails
+        if let Err(err) = sessions[i].cleanup() {
+            abort(
+                &mut sessions[i + 1..],
+                format!("failed to run cleanup: {}", err),
+            );
+        }
+        if let Some(err) = sessions[i].hook_error() {
+            progress!(machine, "{} {}", prompt, err);
+        }
+    }
+
+    // Flag runs disturbed by something other than the target (interrupts, page
+    // cache effects, etc) and optionally discard them
+    for (idx, s) in sessions.iter_mut().enumerate() {
+        let label = if comparing {
+            format!("{}: ", report::compare::label(idx))
+        } else {
+            String::new()
+        };
+
+        for (name, runs) in s.outliers() {
+            if !runs.is_empty() {
+                progress!(
+                    machine,
+                    "{} {}{} of {} runs flagged as outliers for {}",
+                    prompt,
+                    label,
+                    runs.len(),
+                    run_count,
+                    name
+                );
+            }
+        }
+
+        if matchers.is_present("reject-outliers") {
+            let rejected = s.reject_outliers();
+            progress!(
+                machine,
+                "{} {}rejected {} of {} runs as outliers",
+                prompt,
+                label,
+                rejected,
+                run_count
+            );
+        }
+    }
+
+    if let Some(ref baseline) = baseline {
+        for s in &mut sessions {
+            s.compare(baseline);
+        }
+        progress!(
+            machine,
+            "{} comparing against baseline '{}'",
+            prompt,
+            baseline.name()
+        );
+    }
+
+    let reports: Vec<_> = sessions.iter().map(|s| s.report()).collect();
+
+    let written = match format {
+        "json" => write_json(&reports),
+        "csv" => write_csv(&reports, matchers.value_of("separator").unwrap()),
+        _ if comparing => {
+            println!("\n");
+            report::compare::write(&mut io::stdout(), &reports)
+        }
+        _ => {
+            println!("\n");
+            for c in sessions[0].counters() {
+                println!("{}", c);
+            }
+            Ok(())
+        }
+    };
 
-    for i in 0..run_count {
-        let mut runner = runner::Runner::new(target).args(&args);
+    if !machine {
+        println!();
+        for (i, r) in reports.iter().enumerate() {
+            let label = if comparing {
+                format!("{}: ", report::compare::label(i))
+            } else {
+                String::new()
+            };
+            println!("{}{}", label, report::summary(r.runs));
+        }
+
+        for (i, s) in sessions.iter().enumerate() {
+            let label = if comparing {
+                format!("{}: ", report::compare::label(i))
+            } else {
+                String::new()
+            };
+            print_raw(&label, s);
+        }
+    }
+
+    if let (false, Some(group)) = (machine, breakdown(&matchers)) {
+        for (i, s) in sessions.iter().enumerate() {
+            let label = if comparing {
+                format!("{}: ", report::compare::label(i))
+            } else {
+                String::new()
+            };
+            print_breakdown(&label, s, group);
+        }
+    }
+
+    if let Err(err) = written {
+        println!("failed to write report: {}", err);
+        process::exit(-1);
+    }
+
+    if let Some(dir) = matchers.value_of("outdir") {
+        for (i, r) in reports.iter().enumerate() {
+            let path = outdir(i);
+            if let Err(err) = report::raw::write(&path, r.runs, &r.samples) {
+                println!("failed to write raw values to {}: {}", path.display(), err);
+                process::exit(-1);
+            }
+        }
+        progress!(machine, "{} raw values written to {}", prompt, dir);
+    }
+
+    if let Some(name) = matchers.value_of("save-baseline") {
+        match Baseline::save(Path::new(BASELINE_DIR), name, &reports[0].samples) {
+            Ok(path) => progress!(
+                machine,
+                "{} baseline '{}' saved to {}",
+                prompt,
+                name,
+                path.display()
+            ),
+            Err(err) => {
+                println!("failed to save baseline '{}': {}", name, err);
+                process::exit(-1);
+            }
+        }
+    }
+}
+
+/// Returns the words of a command line executing cmd with the shell.
+fn shell_command(cmd: &str) -> Vec<String> {
+    vec![
+        String::from(SHELL),
+        String::from("-c"),
+        String::from(cmd),
+    ]
+}
 
-        let start = Instant::now();
-        if let Some(err) = runner.run(&mut counters).err() {
-            println!("failed to run benchmark: {}", err);
+/// Print msg and exit after running the "cleanup" command of each session, as
+/// the benchmark cannot continue.
+fn abort(sessions: &mut [Session], msg: String) -> ! {
+    println!("{}", msg);
+    for s in sessions {
+        if let Err(err) = s.cleanup() {
+            println!("failed to run cleanup: {}", err);
+        }
+    }
+    process::exit(-1);
+}
+
+/// Print the output captured from the last run of s (if it failed) to stderr.
+fn print_output(s: &Session) {
+    let stderr = io::stderr();
+    let _ = stderr.lock().write_all(s.output());
+}
+
+/// Print the mean value of each event before the overhead was subtracted
+/// alongside the overhead, if calibrated.
+fn print_raw(label: &str, s: &Session) {
+    let raw = s.raw();
+    if raw.is_empty() {
+        return;
+    }
+
+    println!("\n{}before subtracting overhead:", label);
+    for (name, value) in raw {
+        let overhead = s
+            .overhead()
+            .iter()
+            .find(|(event, _)| event == name)
+            .map_or(0, |&(_, v)| v);
+        println!(
+            "{:>30}: {:>14}    (overhead {})",
+            name,
+            (value.round() as u64).separated_string(),
+            overhead.separated_string()
+        );
+    }
+}
+
+/// Returns how the value of each event is broken down by the tasks of the
+/// target, if at all.
+fn breakdown(matchers: &clap::ArgMatches) -> Option<Group> {
+    matchers
+        .value_of("breakdown")
+        .map(|_| value_t!(matchers, "breakdown", Group).unwrap_or_else(|e| e.exit()))
+}
+
+/// Print the mean value of each event per process or thread of the target
+/// alongside the total.
+fn print_breakdown(label: &str, s: &Session, group: Group) {
+    let by = match group {
+        Group::Process => "process",
+        Group::Thread => "thread",
+    };
+
+    for c in s.counters() {
+        for b in c.breakdown(group) {
+            let total: f64 = b.tasks.iter().map(|t| t.1).sum();
+            println!("\n{}{} by {}:", label, b.name, by);
+            for (name, value) in &b.tasks {
+                let pct = if total == 0.0 { 0.0 } else { value * 100.0 / total };
+                println!(
+                    "{:>30}: {:>14}    ({: >5.1}%)",
+                    name,
+                    (value.round() as u64).separated_string(),
+                    pct
+                );
+            }
+        }
+    }
+}
+
+/// Write reports as JSON - a single document for a single target, or an array
+/// of documents when comparing targets.
+fn write_json(reports: &[Report]) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut w = stdout.lock();
+
+    if let [ref report] = *reports {
+        return report::json::write(&mut w, report);
+    }
+
+    writeln!(w, "[")?;
+    for (i, r) in reports.iter().enumerate() {
+        if i > 0 {
+            writeln!(w, ",")?;
+        }
+        report::json::write(&mut w, r)?;
+    }
+    writeln!(w, "]")
+}
+
+/// Write reports as CSV, preceding the rows of each compared target with a
+/// comment line identifying it.
+fn write_csv(reports: &[Report], sep: &str) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut w = stdout.lock();
+
+    for (i, r) in reports.iter().enumerate() {
+        if reports.len() > 1 {
+            writeln!(
+                w,
+                "# {}: {} {}",
+                report::compare::label(i),
+                r.target,
+                r.args.join(" ")
+            )?;
+        }
+        report::csv::write(&mut w, r, sep)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the options used to execute the target, or exits if they are
+/// invalid.
+fn exec_options(matchers: &clap::ArgMatches) -> Options {
+    let mut options = Options::default();
+
+    if let Some(v) = matchers.value_of("timeout") {
+        options.timeout = Some(duration::parse(v).unwrap_or_else(|err| {
+            println!("invalid --timeout: {}", err);
             process::exit(-1);
+        }));
+    }
+
+    let sig = matchers.value_of("timeout-signal").unwrap();
+    options.timeout_signal = sig
+        .parse()
+        .or_else(|_| format!("SIG{}", sig).parse())
+        .unwrap_or_else(|_| {
+            println!("invalid --timeout-signal '{}'", sig);
+            process::exit(-1);
+        });
+
+    options.output = match matchers.value_of("target-output").unwrap() {
+        "null" => Output::Null,
+        "capture" => Output::Capture,
+        // Files are per run, so are configured by the session
+        _ => Output::Inherit,
+    };
+
+    options.input = match matchers.value_of("stdin") {
+        Some("-") => {
+            let mut buf = vec![];
+            if let Err(err) = io::stdin().read_to_end(&mut buf) {
+                println!("failed to read stdin: {}", err);
+                process::exit(-1);
+            }
+            Input::Bytes(buf)
+        }
+        Some(v) => {
+            // Check the file up front so a typo doesn't fail part way through
+            if let Err(err) = std::fs::File::open(v) {
+                println!("invalid --stdin '{}': {}", v, err);
+                process::exit(-1);
+            }
+            Input::File(PathBuf::from(v))
         }
+        None => Input::Inherit,
+    };
 
-        let diff = start.elapsed();
-        let ms = (diff.as_secs() * 1000) + u64::from(diff.subsec_millis());
+    for v in matchers.values_of("env").into_iter().flatten() {
+        match v.find('=') {
+            Some(i) if i > 0 => options.env.push((v[..i].to_string(), v[i + 1..].to_string())),
+            _ => {
+                println!("invalid --env '{}': expected KEY=VAL", v);
+                process::exit(-1);
+            }
+        }
+    }
+    options.env_clear = matchers.is_present("env-clear");
 
-        let progress = Yellow.paint(format!("[{}/{}]", i + 1, run_count));
-        println!("{}{}\truntime: {}ms", prompt, progress, ms);
+    if let Some(v) = matchers.value_of("cwd") {
+        if !Path::new(v).is_dir() {
+            println!("invalid --cwd '{}': not a directory", v);
+            process::exit(-1);
+        }
+        options.cwd = Some(PathBuf::from(v));
     }
 
-    println!("\n");
-    for c in counters {
-        println!("{}", c);
+    if let Some(v) = matchers.value_of("cpu") {
+        options.cpus = Some(cpulist::parse(v).unwrap_or_else(|err| {
+            println!("invalid --cpu: {}", err);
+            process::exit(-1);
+        }));
     }
+
+    options.nice = matchers
+        .value_of("nice")
+        .map(|_| value_t!(matchers, "nice", i32).unwrap_or_else(|e| e.exit()));
+    options.rtprio = matchers
+        .value_of("rtprio")
+        .map(|_| value_t!(matchers, "rtprio", i32).unwrap_or_else(|e| e.exit()));
+
+    options.disable_aslr = matchers.is_present("no-aslr");
+    options.random_env = matchers.is_present("random-env");
+    options.markers = matchers.is_present("markers");
+
+    options
+}
+
+/// Returns the measures of central tendency and dispersion to display.
+#[cfg(any(target_os = "freebsd", target_os = "linux"))]
+fn summary(matchers: &clap::ArgMatches) -> (Center, Spread) {
+    (
+        value_t!(matchers, "center", Center).unwrap_or_else(|e| e.exit()),
+        value_t!(matchers, "spread", Spread).unwrap_or_else(|e| e.exit()),
+    )
 }
 
-#[cfg(not(target_os = "freebsd"))]
+#[cfg(not(any(target_os = "freebsd", target_os = "linux")))]
 fn get_counters<'a>(
     _matchers: &'a clap::ArgMatches<'a>,
 ) -> Result<Vec<Box<dyn DisplayCounter + 'a>>, String> {
     Ok(vec![Box::new(MockEvent::new("mock", 42))])
 }
 
+#[cfg(target_os = "linux")]
+fn get_counters<'a>(
+    matchers: &'a clap::ArgMatches<'a>,
+) -> Result<Vec<Box<dyn DisplayCounter + 'a>>, String> {
+    let mut counters: Vec<Box<dyn DisplayCounter>> = vec![];
+
+    let (center, spread) = summary(matchers);
+    let printer = |c| RSDPrinter::new(c).center(center).spread(spread);
+
+    // Count every process in a shell command line
+    let group = breakdown(matchers);
+    let inherit = matchers.is_present("shell") || matchers.is_present("inherit");
+    let new = |spec| {
+        PerfEvent::new(spec).map(|c| c.inherit(inherit).breakdown(group.is_some()))
+    };
+
+    // Allocate user specified events
+    if matchers.is_present("event-spec") {
+        for event in matchers.values_of("event-spec").unwrap() {
+            counters.push(Box::new(printer(
+                new(event).map_err(|e| format!("{}: {}", event, e))?,
+            )));
+        }
+
+        return Ok(counters);
+    }
+
+    // Otherwise use the defaults.
+    //
+    // Hardware events are unavailable when running without a PMU (i.e. in most
+    // VMs) so failing to allocate them is not fatal.
+    match new("instructions") {
+        Ok(instructions) => {
+            let defaults = [
+                ("cycles", "unhalted-cycles"),
+                ("branches", "speculated-good"),
+                ("branch-misses", "speculated-bad"),
+            ];
+
+            let mut comparators = vec![];
+            for &(event, alias) in &defaults {
+                if let Ok(counter) = new(event)
+                    .map_err(|e| eprintln!("{}: {}", event, e))
+                    .map(|c| c.alias(alias))
+                {
+                    comparators.push(printer(counter));
+                }
+            }
+
+            // Push the instructions counter, along with all the default
+            // comparators (which are expressed as a relative of instructions)
+            counters.push(Box::new(RelativePrinter::new(
+                printer(instructions),
+                comparators,
+            )));
+        }
+        Err(e) => eprintln!("instructions: {}", e),
+    }
+
+    // Attempt to allocate and push the cache counters
+    if let Ok(refs) = new("cache-references")
+        .map_err(|e| eprintln!("cache-references: {}", e))
+    {
+        let refs = printer(refs);
+
+        // Attempt to build a relative pair
+        let counter: Box<dyn DisplayCounter> = match new("cache-misses") {
+            Ok(misses) => Box::new(RelativePrinter::new(refs, vec![printer(misses)])),
+            Err(e) => {
+                // Push the successful refs counter only
+                eprintln!("cache-misses: {}", e);
+                Box::new(refs)
+            }
+        };
+
+        counters.push(counter);
+    }
+
+    // Software events are always available
+    let software = [
+        "task-clock",
+        "context-switches",
+        "cpu-migrations",
+        "page-faults",
+    ];
+
+    for &event in &software {
+        counters.push(Box::new(printer(
+            new(event).map_err(|e| format!("{}: {}", event, e))?,
+        )));
+    }
+
+    Ok(counters)
+}
+
 #[cfg(target_os = "freebsd")]
 fn get_counters<'a>(
     matchers: &'a clap::ArgMatches<'a>,
 ) -> Result<Vec<Box<DisplayCounter + 'a>>, String> {
     let mut counters: Vec<Box<DisplayCounter>> = vec![];
 
+    let (center, spread) = summary(matchers);
+    let printer = |c| RSDPrinter::new(c).center(center).spread(spread);
+
+    // Count every process started by the target
+    let inherit = matchers.is_present("shell") || matchers.is_present("inherit");
+    let new = |spec| PmcEvent::new(spec).and_then(|c| c.inherit(inherit));
+
     // Allocate user specified events
     if matchers.is_present("event-spec") {
         for event in matchers.values_of("event-spec").unwrap() {
-            counters.push(Box::new(RSDPrinter::new(
-                PmcEvent::new(event).map_err(|e| format!("{}: {}", event, e))?,
+            counters.push(Box::new(printer(
+                new(event).map_err(|e| format!("{}: {}", event, e))?,
             )));
         }
 
@@ -166,7 +1284,7 @@ fn get_counters<'a>(
     }
 
     let instructions =
-        PmcEvent::new("instructions").map_err(|e| format!("initialising counter: {}", e))?;
+        new("instructions").map_err(|e| format!("initialising counter: {}", e))?;
 
     // Otherwise use the defaults
     let defaults = [
@@ -179,38 +1297,38 @@ fn get_counters<'a>(
 
     let mut comparators = vec![];
     for &(event, alias) in &defaults {
-        if let Ok(counter) = PmcEvent::new(event)
-            .map_err(|e| println!("{}: {}", event, e))
+        if let Ok(counter) = new(event)
+            .map_err(|e| eprintln!("{}: {}", event, e))
             .map(|c| c.alias(alias))
         {
-            comparators.push(Box::new(RSDPrinter::new(counter)));
+            comparators.push(Box::new(printer(counter)));
         }
     }
 
     // Push the instructions counter, along with all the default comparators
     // (which are expressed as a relative of instructions)
     counters.push(Box::new(RelativePrinter::new(
-        RSDPrinter::new(instructions),
+        printer(instructions),
         comparators,
     )));
 
     // Attempt to allocate and push the cache counters
-    if let Ok(refs) = PmcEvent::new("LONGEST_LAT_CACHE.REFERENCE")
-        .map_err(|e| println!("LONGEST_LAT_CACHE.REFERENCE: {}", e))
+    if let Ok(refs) = new("LONGEST_LAT_CACHE.REFERENCE")
+        .map_err(|e| eprintln!("LONGEST_LAT_CACHE.REFERENCE: {}", e))
         .map(|c| c.alias("cache-references"))
     {
         // Wrap the cache references in a RSDPrinter
-        let refs = RSDPrinter::new(refs);
+        let refs = printer(refs);
 
         // Attempt to build a relative pair
-        let counter: Box<DisplayCounter> = match PmcEvent::new("LONGEST_LAT_CACHE.MISS") {
+        let counter: Box<DisplayCounter> = match new("LONGEST_LAT_CACHE.MISS") {
             Ok(misses) => Box::new(RelativePrinter::new(
                 refs,
-                vec![Box::new(RSDPrinter::new(misses.alias("cache-misses")))],
+                vec![Box::new(printer(misses.alias("cache-misses")))],
             )),
             Err(e) => {
                 // Push the successful refs counter only
-                println!("LONGEST_LAT_CACHE.MISS: {}", e);
+                eprintln!("LONGEST_LAT_CACHE.MISS: {}", e);
                 Box::new(refs)
             }
         };
@@ -0,0 +1,182 @@
+use report::Report;
+use stats;
+
+use separator::Separatable;
+use std::io::{self, Write};
+
+/// Returns the label used to refer to the i-th compared target.
+pub fn label(i: usize) -> String {
+	if i < 26 {
+		return ((b'A' + i as u8) as char).to_string();
+	}
+	(i + 1).to_string()
+}
+
+/// Write a side-by-side comparison of the mean value of each event across
+/// reports, with the ratio of each mean relative to the first report and the
+/// significance of the difference (see [`Comparison`]):
+///
+/// ```text
+///   A: ./algorithms.test -test.bench=BenchmarkSampleA
+///   B: ./algorithms.test -test.bench=BenchmarkSampleB
+///
+///                                              A              B
+///                   instructions: 19,031,333,328 15,185,065,698    0.798x p=0.000 u=0.000 [-20.3%, -20.1%]  *
+///             RESOURCE_STALLS.SB:        432,623        436,014    1.008x p=0.412 u=0.450 [-1.1%, +2.7%]
+/// ```
+///
+/// Differences marked with `*` are statistically significant.
+///
+/// [`Comparison`]: ../../stats/struct.Comparison.html
+pub fn write<W: Write>(w: &mut W, reports: &[Report]) -> io::Result<()> {
+	let first = match reports.first() {
+		Some(r) => r,
+		None => return Ok(()),
+	};
+
+	for (i, r) in reports.iter().enumerate() {
+		writeln!(w, "{:>3}: {} {}", label(i), r.target, r.args.join(" "))?;
+	}
+	writeln!(w)?;
+
+	write!(w, "{:>32}", "")?;
+	for i in 0..reports.len() {
+		write!(w, "{:>14} ", label(i))?;
+	}
+	writeln!(w)?;
+
+	for s in &first.samples {
+		write!(w, "{:>30}: ", s.name)?;
+
+		let values: Vec<_> = reports
+			.iter()
+			.map(|r| r.samples.iter().find(|o| o.name == s.name).map(|o| o.values))
+			.collect();
+
+		for v in &values {
+			match *v {
+				Some(v) => write!(w, "{:>14} ", (stats::mean(v) as u64).separated_string())?,
+				None => write!(w, "{:>14} ", "-")?,
+			}
+		}
+
+		for v in values.iter().skip(1) {
+			let v = match *v {
+				Some(v) => v,
+				None => continue,
+			};
+
+			let base = stats::mean(s.values);
+			if base == 0.0 {
+				write!(w, "  {:>7}", "-")?;
+			} else {
+				write!(w, "  {:>6.3}x", stats::mean(v) / base)?;
+			}
+
+			match stats::compare(s.values, v) {
+				Some(c) => write!(w, " {}", c)?,
+				None => write!(w, " p=-     u=-     [-]                 ")?,
+			}
+		}
+
+		writeln!(w)?;
+	}
+
+	writeln!(w)?;
+	writeln!(
+		w,
+		"  p: Welch's t-test, u: Mann-Whitney U test, [..]: {}% confidence interval of the difference to {}",
+		stats::CONFIDENCE * 100.0,
+		label(0),
+	)?;
+	writeln!(
+		w,
+		"  *: the difference to {} is statistically significant (p < {})",
+		label(0),
+		stats::SIGNIFICANCE
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event::Sample;
+	use report::Run;
+
+	#[test]
+	fn test_label() {
+		assert_eq!(label(0), "A");
+		assert_eq!(label(1), "B");
+		assert_eq!(label(25), "Z");
+		assert_eq!(label(26), "27");
+	}
+
+	#[test]
+	fn test_write() {
+		let runs: &[Run] = &[];
+		let a = Report {
+			target: "./a",
+			args: &["-x"],
+			runs,
+			samples: vec![
+				Sample {
+					name: "instructions",
+					spec: "instructions",
+					values: &[1000, 1010, 990],
+					relative: None,
+				},
+				Sample {
+					name: "cycles",
+					spec: "cycles",
+					values: &[0, 0],
+					relative: None,
+				},
+			],
+		};
+		let b = Report {
+			target: "./b",
+			args: &[],
+			runs,
+			samples: vec![
+				Sample {
+					name: "instructions",
+					spec: "instructions",
+					values: &[2000, 2010, 1990],
+					relative: None,
+				},
+				Sample {
+					name: "cycles",
+					spec: "cycles",
+					values: &[1],
+					relative: None,
+				},
+			],
+		};
+
+		let mut buf = vec![];
+		write(&mut buf, &[a, b]).unwrap();
+
+		let got = String::from_utf8(buf).unwrap();
+		let lines: Vec<_> = got.lines().collect();
+		assert_eq!(lines[0], "  A: ./a -x");
+		assert_eq!(lines[1], "  B: ./b ");
+		assert_eq!(
+			lines[3],
+			format!("{:>32}{:>14} {:>14} ", "", "A", "B")
+		);
+		assert_eq!(
+			lines[4],
+			format!(
+				"{:>30}: {:>14} {:>14}    2.000x p=0.000 u=0.081 [+97.7%, +102.3%] *",
+				"instructions", "1,000", "2,000"
+			)
+		);
+		assert_eq!(
+			lines[5],
+			format!(
+				"{:>30}: {:>14} {:>14}         - p=-     u=-     [-]                 ",
+				"cycles", "0", "1"
+			)
+		);
+	}
+}
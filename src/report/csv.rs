@@ -0,0 +1,104 @@
+use report::Report;
+use stats;
+
+use std::io::{self, Write};
+
+/// Write one row per event in the field layout used by `perf stat -x`:
+///
+/// ```text
+/// value,unit,event,variance,run-time,percentage,metric-value,metric-unit
+/// ```
+///
+/// The variance field is the relative standard deviation across runs, and the
+/// metric fields carry the value relative to another event (as displayed by the
+/// text output) when configured:
+///
+/// ```text
+/// 19031333328,,instructions,0.00%,,,,
+/// 7002094130,,unhalted-cycles,4.20%,,,36.79,of instructions
+/// ```
+///
+/// Counter run time and multiplexing percentage are not tracked so those fields
+/// are left empty, and as raw counter values have no unit neither is the unit.
+pub fn write<W: Write>(w: &mut W, report: &Report, sep: &str) -> io::Result<()> {
+	for s in &report.samples {
+		let (metric, unit) = match s.relative {
+			Some(ref r) if r.percent.is_finite() => {
+				(format!("{:.2}", r.percent), format!("of {}", r.to))
+			}
+			_ => (String::new(), String::new()),
+		};
+
+		let fields = [
+			(stats::mean(s.values) as u64).to_string(),
+			String::new(),
+			s.name.to_string(),
+			format!("{:.2}%", stats::rsd(s.values)),
+			String::new(),
+			String::new(),
+			metric,
+			unit,
+		];
+
+		writeln!(w, "{}", fields.join(sep))?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event::{Relative, Sample};
+	use report::Run;
+
+	#[test]
+	fn test_write() {
+		let report = Report {
+			target: "/bin/true",
+			args: &[],
+			runs: &[] as &[Run],
+			samples: vec![
+				Sample {
+					name: "instructions",
+					spec: "instructions",
+					values: &[10, 30],
+					relative: None,
+				},
+				Sample {
+					name: "unhalted-cycles",
+					spec: "cycles",
+					values: &[5, 5],
+					relative: Some(Relative {
+						to: "instructions",
+						percent: 25.0,
+					}),
+				},
+				Sample {
+					name: "cache-misses",
+					spec: "cache-misses",
+					values: &[1, 1],
+					relative: Some(Relative {
+						to: "cache-references",
+						percent: f64::INFINITY,
+					}),
+				},
+			],
+		};
+
+		let mut buf = vec![];
+		write(&mut buf, &report, ",").unwrap();
+		assert_eq!(
+			String::from_utf8(buf).unwrap(),
+			"20,,instructions,70.71%,,,,\n\
+			 5,,unhalted-cycles,0.00%,,,25.00,of instructions\n\
+			 1,,cache-misses,0.00%,,,,\n"
+		);
+
+		let mut buf = vec![];
+		write(&mut buf, &report, ";").unwrap();
+		assert!(String::from_utf8(buf)
+			.unwrap()
+			.starts_with("20;;instructions;70.71%;;;;\n"));
+	}
+}
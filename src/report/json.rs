@@ -0,0 +1,247 @@
+use report::Report;
+use runner::Status;
+use stats;
+
+use std::io::{self, Write};
+
+/// Write report as a JSON document:
+///
+/// ```text
+/// {
+///   "target": "./algorithms.test",
+///   "args": ["-test.bench=BenchmarkSampleA"],
+///   "count": 2,
+///   "runs": [{"runtime_us": 1503, "status": 0, "signal": null, "timed_out": false, "excluded": false}, ...],
+///   "events": [
+///     {
+///       "name": "unhalted-cycles",
+///       "spec": "cycles",
+///       "alias": "unhalted-cycles",
+///       "samples": [7002094130, 7002094122],
+///       "mean": 7002094126,
+///       "stddev": 5.65,
+///       "rsd": 0.0,
+///       "median": 7002094126,
+///       "min": 7002094122,
+///       "max": 7002094130,
+///       "p5": 7002094122.4,
+///       "p95": 7002094129.6,
+///       "mad": 4,
+///       "relative": {"to": "instructions", "percent": 36.8}
+///     },
+///     ...
+///   ]
+/// }
+/// ```
+///
+/// The `status` of a run is the exit code of the target, or null if it was
+/// terminated by the named `signal` or `timed_out`. The `samples` of each
+/// event have no entry for runs that are `excluded` from the results.
+///
+/// `relative` is null for events not displayed relative to another event.
+pub fn write<W: Write>(w: &mut W, report: &Report) -> io::Result<()> {
+	writeln!(w, "{{")?;
+	writeln!(w, "  \"target\": {},", string(report.target))?;
+	writeln!(w, "  \"args\": [{}],", join(report.args.iter().map(|a| string(a))))?;
+	writeln!(w, "  \"count\": {},", report.runs.len())?;
+	writeln!(
+		w,
+		"  \"runs\": [{}],",
+		join(report.runs.iter().map(|r| {
+			let null = || String::from("null");
+			let (status, signal) = match r.status {
+				Status::Exited(code) => (code.to_string(), null()),
+				Status::Signaled(sig) => (null(), string(sig.as_str())),
+				Status::TimedOut => (null(), null()),
+			};
+			format!(
+				"{{\"runtime_us\": {}, \"status\": {}, \"signal\": {}, \"timed_out\": {}, \"excluded\": {}}}",
+				r.runtime.as_micros(),
+				status,
+				signal,
+				r.status == Status::TimedOut,
+				r.excluded
+			)
+		}))
+	)?;
+
+	writeln!(w, "  \"events\": [")?;
+	for (i, s) in report.samples.iter().enumerate() {
+		writeln!(w, "    {{")?;
+		writeln!(w, "      \"name\": {},", string(s.name))?;
+		writeln!(w, "      \"spec\": {},", string(s.spec))?;
+		writeln!(
+			w,
+			"      \"alias\": {},",
+			s.alias().map(string).unwrap_or_else(|| String::from("null"))
+		)?;
+		writeln!(
+			w,
+			"      \"samples\": [{}],",
+			join(s.values.iter().map(|v| v.to_string()))
+		)?;
+		writeln!(w, "      \"mean\": {},", number(stats::mean(s.values)))?;
+		writeln!(w, "      \"stddev\": {},", number(stats::stddev(s.values)))?;
+		writeln!(w, "      \"rsd\": {},", number(stats::rsd(s.values)))?;
+		writeln!(w, "      \"median\": {},", number(stats::median(s.values)))?;
+		writeln!(w, "      \"min\": {},", number(stats::min(s.values)))?;
+		writeln!(w, "      \"max\": {},", number(stats::max(s.values)))?;
+		writeln!(w, "      \"p5\": {},", number(stats::percentile(s.values, 5.0)))?;
+		writeln!(w, "      \"p95\": {},", number(stats::percentile(s.values, 95.0)))?;
+		writeln!(w, "      \"mad\": {},", number(stats::mad(s.values)))?;
+		match s.relative {
+			Some(ref r) => writeln!(
+				w,
+				"      \"relative\": {{\"to\": {}, \"percent\": {}}}",
+				string(r.to),
+				number(r.percent)
+			)?,
+			None => writeln!(w, "      \"relative\": null")?,
+		}
+
+		let sep = if i + 1 < report.samples.len() { "," } else { "" };
+		writeln!(w, "    }}{}", sep)?;
+	}
+	writeln!(w, "  ]")?;
+	writeln!(w, "}}")
+}
+
+fn join<I: Iterator<Item = String>>(iter: I) -> String {
+	iter.collect::<Vec<_>>().join(", ")
+}
+
+/// Encode s as a JSON string.
+fn string(s: &str) -> String {
+	let mut out = String::with_capacity(s.len() + 2);
+	out.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\r' => out.push_str("\\r"),
+			'\t' => out.push_str("\\t"),
+			c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+	out
+}
+
+/// Encode v as a JSON number - JSON has no representation for NaN or infinity
+/// (i.e. a percentage relative to a zero value) so these become null.
+fn number(v: f64) -> String {
+	if !v.is_finite() {
+		return String::from("null");
+	}
+	v.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event::{Relative, Sample};
+	use report::Run;
+	use std::time::Duration;
+
+	#[test]
+	fn test_string() {
+		assert_eq!(string("plain"), "\"plain\"");
+		assert_eq!(string("a \"b\"\\"), "\"a \\\"b\\\"\\\\\"");
+		assert_eq!(string("\n\t\u{1}"), "\"\\n\\t\\u0001\"");
+	}
+
+	#[test]
+	fn test_number() {
+		assert_eq!(number(20.0), "20");
+		assert_eq!(number(36.8), "36.8");
+		assert_eq!(number(f64::NAN), "null");
+		assert_eq!(number(f64::INFINITY), "null");
+	}
+
+	#[test]
+	fn test_write() {
+		let runs = vec![
+			Run {
+				runtime: Duration::from_micros(10),
+				status: Status::Exited(0),
+				excluded: false,
+			},
+			Run {
+				runtime: Duration::from_micros(20),
+				status: Status::Exited(0),
+				excluded: false,
+			},
+		];
+
+		let report = Report {
+			target: "/bin/true",
+			args: &["-a"],
+			runs: &runs,
+			samples: vec![
+				Sample {
+					name: "instructions",
+					spec: "instructions",
+					values: &[10, 30],
+					relative: None,
+				},
+				Sample {
+					name: "unhalted-cycles",
+					spec: "cycles",
+					values: &[5, 5],
+					relative: Some(Relative {
+						to: "instructions",
+						percent: 25.0,
+					}),
+				},
+			],
+		};
+
+		let mut buf = vec![];
+		write(&mut buf, &report).unwrap();
+
+		let want = r#"{
+  "target": "/bin/true",
+  "args": ["-a"],
+  "count": 2,
+  "runs": [{"runtime_us": 10, "status": 0, "signal": null, "timed_out": false, "excluded": false}, {"runtime_us": 20, "status": 0, "signal": null, "timed_out": false, "excluded": false}],
+  "events": [
+    {
+      "name": "instructions",
+      "spec": "instructions",
+      "alias": null,
+      "samples": [10, 30],
+      "mean": 20,
+      "stddev": 14.142135623730951,
+      "rsd": 70.71067811865476,
+      "median": 20,
+      "min": 10,
+      "max": 30,
+      "p5": 11,
+      "p95": 29,
+      "mad": 10,
+      "relative": null
+    },
+    {
+      "name": "unhalted-cycles",
+      "spec": "cycles",
+      "alias": "unhalted-cycles",
+      "samples": [5, 5],
+      "mean": 5,
+      "stddev": 0,
+      "rsd": 0,
+      "median": 5,
+      "min": 5,
+      "max": 5,
+      "p5": 5,
+      "p95": 5,
+      "mad": 0,
+      "relative": {"to": "instructions", "percent": 25}
+    }
+  ]
+}
+"#;
+		assert_eq!(String::from_utf8(buf).unwrap(), want);
+	}
+}
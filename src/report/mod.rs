@@ -0,0 +1,100 @@
+pub mod compare;
+pub mod csv;
+pub mod json;
+pub mod raw;
+
+use event::Sample;
+use runner::Status;
+use std::time::Duration;
+
+/// `Run` records the outcome of a single measured execution of the target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+	pub runtime: Duration,
+	pub status: Status,
+
+	/// Set when the values observed in this run are excluded from the results
+	/// (i.e. the run failed, or was rejected as an outlier) - excluded runs
+	/// have no entry in the values of a [`Sample`].
+	///
+	/// [`Sample`]: ../event/struct.Sample.html
+	pub excluded: bool,
+}
+
+/// `Report` describes a completed benchmark session, and is rendered by the
+/// machine-readable output formats.
+pub struct Report<'a> {
+	pub target: &'a str,
+	pub args: &'a [&'a str],
+	pub runs: &'a [Run],
+	pub samples: Vec<Sample<'a>>,
+}
+
+/// values pairs each run with the value observed for it in values, or None if
+/// the run is excluded from the results.
+pub fn values<'a>(runs: &'a [Run], values: &'a [u64]) -> impl Iterator<Item = (&'a Run, Option<u64>)> {
+	let mut values = values.iter();
+	runs.iter().map(move |r| {
+		if r.excluded {
+			(r, None)
+		} else {
+			(r, values.next().cloned())
+		}
+	})
+}
+
+/// Returns a one line summary of how the runs terminated and how many were
+/// excluded from the results, i.e.:
+///
+/// ```text
+/// 10 runs: 8 succeeded, 1 failed, 1 timed out, 2 excluded
+/// ```
+pub fn summary(runs: &[Run]) -> String {
+	let count = |f: &dyn Fn(&Run) -> bool| runs.iter().filter(|r| f(r)).count();
+
+	format!(
+		"{} {}: {} succeeded, {} failed, {} timed out, {} excluded",
+		runs.len(),
+		if runs.len() == 1 { "run" } else { "runs" },
+		count(&|r| r.status.success()),
+		count(&|r| !r.status.success() && r.status != Status::TimedOut),
+		count(&|r| r.status == Status::TimedOut),
+		count(&|r| r.excluded)
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nix::sys::signal::Signal;
+
+	#[test]
+	fn test_summary() {
+		let run = |status, excluded| Run {
+			runtime: Duration::from_millis(1),
+			status,
+			excluded,
+		};
+
+		let runs = vec![
+			run(Status::Exited(0), false),
+			run(Status::Exited(0), true),
+			run(Status::Exited(1), false),
+			run(Status::Signaled(Signal::SIGSEGV), true),
+			run(Status::TimedOut, true),
+		];
+
+		assert_eq!(
+			summary(&runs),
+			"5 runs: 2 succeeded, 2 failed, 1 timed out, 3 excluded"
+		);
+		assert_eq!(
+			summary(&runs[..1]),
+			"1 run: 1 succeeded, 0 failed, 0 timed out, 0 excluded"
+		);
+		assert_eq!(
+			summary(&[]),
+			"0 runs: 0 succeeded, 0 failed, 0 timed out, 0 excluded"
+		);
+	}
+}
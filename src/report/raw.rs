@@ -0,0 +1,194 @@
+use event::Sample;
+use report::{self, Run};
+use runner::Status;
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Write the raw observed values of each event to a file in dir, alongside the
+/// runtime and exit status of the run that produced them.
+///
+/// One file is written per event, named after the event (with a `-2`, `-3`,
+/// ... suffix if another event has the same file name) and containing one line
+/// per run:
+///
+/// ```text
+/// run,runtime_us,status,value
+/// 1,1503,0,19031333328
+/// 2,1498,0,19031333301
+/// 3,1455,SIGSEGV,
+/// ```
+///
+/// The status is the exit code of the target, the name of the signal that
+/// terminated it, or `timeout`. The value of a run excluded from the results
+/// is empty.
+///
+/// The directory is created if it does not already exist.
+pub fn write(dir: &Path, runs: &[Run], samples: &[Sample]) -> io::Result<Vec<PathBuf>> {
+	fs::create_dir_all(dir)?;
+
+	let mut paths = Vec::with_capacity(samples.len());
+	let mut used = HashSet::new();
+	for sample in samples {
+		// The same event may be measured twice, or two events may only differ
+		// in characters replaced in the file name
+		let name = file_name(sample.name);
+		let mut unique = name.clone();
+		for n in 2.. {
+			if used.insert(unique.clone()) {
+				break;
+			}
+			unique = format!("{}-{}", name, n);
+		}
+
+		let path = dir.join(format!("{}.csv", unique));
+		let mut w = BufWriter::new(File::create(&path)?);
+
+		writeln!(w, "run,runtime_us,status,value")?;
+		for (i, (run, value)) in report::values(runs, sample.values).enumerate() {
+			writeln!(
+				w,
+				"{},{},{},{}",
+				i + 1,
+				run.runtime.as_micros(),
+				status(run.status),
+				value.map(|v| v.to_string()).unwrap_or_default()
+			)?;
+		}
+
+		w.flush()?;
+		paths.push(path);
+	}
+
+	Ok(paths)
+}
+
+/// Format status as the exit code, or the name of the terminating signal.
+fn status(status: Status) -> String {
+	match status {
+		Status::Exited(code) => code.to_string(),
+		Status::Signaled(sig) => sig.as_str().to_string(),
+		Status::TimedOut => String::from("timeout"),
+	}
+}
+
+/// Event specifiers may contain qualifiers and separators (i.e.
+/// `UOPS_RETIRED.ALL,cmask=1` or `task-clock:u`) so replace anything that
+/// isn't safe to use in a file name.
+fn file_name(name: &str) -> String {
+	name.chars()
+		.map(|c| match c {
+			'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+			_ => '_',
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nix::sys::signal::Signal;
+	use std::process;
+	use std::time::Duration;
+
+	#[test]
+	fn test_file_name() {
+		assert_eq!(file_name("RESOURCE_STALLS.ANY"), "RESOURCE_STALLS.ANY");
+		assert_eq!(file_name("task-clock:u"), "task-clock_u");
+		assert_eq!(file_name("UOPS,cmask=1/x"), "UOPS_cmask_1_x");
+	}
+
+	#[test]
+	fn test_write() {
+		let dir = std::env::temp_dir().join(format!("benchpmc-raw-{}", process::id()));
+
+		let runs = vec![
+			Run {
+				runtime: Duration::from_micros(1503),
+				status: Status::Exited(0),
+				excluded: false,
+			},
+			Run {
+				runtime: Duration::from_micros(1455),
+				status: Status::Signaled(Signal::SIGSEGV),
+				excluded: true,
+			},
+			Run {
+				runtime: Duration::from_micros(1498),
+				status: Status::Exited(0),
+				excluded: false,
+			},
+		];
+
+		let samples = vec![
+			Sample {
+				name: "instructions",
+				spec: "instructions",
+				values: &[42, 24],
+				relative: None,
+			},
+			Sample {
+				name: "task-clock:u",
+				spec: "task-clock:u",
+				values: &[1, 2],
+				relative: None,
+			},
+		];
+
+		let paths = write(&dir, &runs, &samples).unwrap();
+		assert_eq!(
+			paths,
+			vec![dir.join("instructions.csv"), dir.join("task-clock_u.csv")]
+		);
+
+		let got = fs::read_to_string(&paths[0]).unwrap();
+		assert_eq!(
+			got,
+			"run,runtime_us,status,value\n1,1503,0,42\n2,1455,SIGSEGV,\n3,1498,0,24\n"
+		);
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_write_duplicate() {
+		let dir = std::env::temp_dir().join(format!("benchpmc-raw-dup-{}", process::id()));
+
+		let runs = vec![Run {
+			runtime: Duration::from_micros(1503),
+			status: Status::Exited(0),
+			excluded: false,
+		}];
+
+		let sample = |name, values| Sample {
+			name,
+			spec: name,
+			values,
+			relative: None,
+		};
+		let samples = vec![
+			sample("task-clock", &[1]),
+			sample("task-clock", &[2]),
+			sample("task-clock:u", &[3]),
+			sample("task-clock_u", &[4]),
+		];
+
+		let paths = write(&dir, &runs, &samples).unwrap();
+		assert_eq!(
+			paths,
+			vec![
+				dir.join("task-clock.csv"),
+				dir.join("task-clock-2.csv"),
+				dir.join("task-clock_u.csv"),
+				dir.join("task-clock_u-2.csv"),
+			]
+		);
+
+		let got = fs::read_to_string(&paths[1]).unwrap();
+		assert_eq!(got, "run,runtime_us,status,value\n1,1503,0,2\n");
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -1,20 +1,117 @@
-use std::ffi::{CString, NulError};
-use std::os::unix::io::RawFd;
-use std::process;
+use std::ffi::{CStr, CString, NulError};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
-use nix::sys::signal::{kill, Signal};
-use nix::sys::socket::{socketpair, AddressFamily, SockFlag, SockType};
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, open, FcntlArg, FdFlag, OFlag};
+use nix::sys::signal::{kill, pthread_sigmask, SigSet, SigmaskHow, Signal};
+use nix::sys::socket::{shutdown, socketpair, AddressFamily, Shutdown, SockFlag, SockType};
+use nix::sys::stat::Mode;
 use nix::sys::wait::{waitpid, WaitStatus};
-use nix::unistd::{close, execvp, fork, read, write, ForkResult, Pid};
+use nix::libc;
+use nix::unistd::{
+	chdir, close, dup2, fork, getpgrp, pipe, read, setpgid, tcgetpgrp, tcsetpgrp, write,
+	ForkResult, Pid,
+};
 
-/// `BAD_EXEC` is returned when the child fails to execute the target process.
-const BAD_EXEC: i32 = 42;
+use error::Error;
+use runner::{layout, sched};
+use runner::{Input, Marker, Output, Status};
+
+/// `EXEC_FAILED` is the exit code of the child if it fails to execute the
+/// target process (as used by sh(1) for commands that cannot be found).
+const EXEC_FAILED: i32 = 127;
+
+/// `KILL_GRACE` is how long a timed out target is given to exit after being
+/// signalled before it is sent `SIGKILL`.
+const KILL_GRACE: Duration = Duration::from_secs(1);
+
+/// `CONTROL_FD` is the descriptor of the control socket in the target (see
+/// [`Exec::control`]) - a single digit, so sh(1) scripts can redirect to it.
+///
+/// [`Exec::control`]: struct.Exec.html#method.control
+pub const CONTROL_FD: RawFd = 3;
+
+/// `ACK` is written to the control socket once the counters have been started
+/// or stopped in response to a marker - a newline, so sh(1) can `read` it.
+const ACK: &[u8] = b"\n";
+
+/// `Step` is a step taken by the child to execute the target, the failure of
+/// which is reported to the parent along with the errno.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+	Exec = 0,
+	Chdir,
+	Affinity,
+	Nice,
+	RtPrio,
+	Aslr,
+}
+
+impl Step {
+	fn from_u8(v: u8) -> Option<Step> {
+		[
+			Step::Exec,
+			Step::Chdir,
+			Step::Affinity,
+			Step::Nice,
+			Step::RtPrio,
+			Step::Aslr,
+		]
+		.get(v as usize)
+		.cloned()
+	}
+
+	/// Returns the error reported for a failure of this step with errno.
+	fn error(self, errno: Errno) -> Error {
+		match self {
+			Step::Exec => Error::ExecFailed(errno),
+			Step::Chdir => Error::SetupFailed("change directory", errno),
+			Step::Affinity => Error::SetupFailed("set CPU affinity", errno),
+			Step::Nice => Error::SetupFailed("set nice value", errno),
+			Step::RtPrio => Error::SetupFailed("set realtime priority", errno),
+			Step::Aslr => Error::SetupFailed("disable ASLR", errno),
+		}
+	}
+}
 
 /// Exec executes the target process, returning a Child process that blocks for
 /// a start signal.
 pub struct Exec {
 	target: CString,
 	args: Vec<CString>,
+	timeout: Option<(Duration, Signal)>,
+
+	/// The complete environment of the target, or None to inherit it.
+	env: Option<Vec<CString>>,
+	/// The working directory of the target, or None to inherit it.
+	cwd: Option<PathBuf>,
+
+	/// The CPUs the target is restricted to, or None for any CPU.
+	cpus: Option<Vec<usize>>,
+	nice: Option<i32>,
+	rtprio: Option<i32>,
+	aslr: bool,
+
+	/// The descriptor the target's stdout and stderr are redirected to.
+	output: Option<RawFd>,
+	/// The read end of the pipe capturing output.
+	capture: Option<RawFd>,
+
+	/// The descriptor the target's stdin is redirected to.
+	input: Option<RawFd>,
+	/// The write end of the pipe feeding stdin, and the bytes to write to it.
+	feed: Option<(RawFd, Vec<u8>)>,
+
+	/// The parent and child ends of the control socket.
+	control: Option<(RawFd, RawFd)>,
 }
 
 /// Child represents the forked process that is blocking for the start signal.
@@ -23,6 +120,13 @@ pub struct Exec {
 pub struct Child {
 	socket: RawFd,
 	pid: Option<Pid>,
+	timeout: Option<(Duration, Signal)>,
+	capture: Option<JoinHandle<Vec<u8>>>,
+	/// The parent end of the control socket.
+	control: Option<RawFd>,
+	/// The terminal the target is the foreground process group of, and the
+	/// process group to restore once it terminates.
+	terminal: Option<(RawFd, Pid)>,
 }
 
 /// Exec handles the execution of a child process.
@@ -35,20 +139,39 @@ pub struct Child {
 /// executes the target by calling exec().
 impl Exec {
 	pub fn new(target: &str) -> Result<Self, NulError> {
+		let target = CString::new(target)?;
 		Ok(Exec {
-			target: CString::new(target)?,
-			args: Vec::new(),
+			// By convention the first argument is the name of the program
+			args: vec![target.clone()],
+			target,
+			timeout: None,
+			env: None,
+			cwd: None,
+			cpus: None,
+			nice: None,
+			rtprio: None,
+			aslr: true,
+			output: None,
+			capture: None,
+			input: None,
+			feed: None,
+			control: None,
 		})
 	}
 
 	/// Execute a child process.
 	///
-	/// If the target process fails to run, the child process exists with
-	/// an exit code of `BAD_EXEC`.
-	pub fn exec(self) -> Child {
+	/// If the target process fails to run, the child process reports the
+	/// `execvp` errno to the parent over the socket used to signal the start,
+	/// and exits with an exit code of `EXEC_FAILED`.
+	pub fn exec(mut self) -> Child {
 		let mut c = Child {
 			pid: None,
 			socket: 0,
+			timeout: self.timeout,
+			capture: None,
+			control: None,
+			terminal: None,
 		};
 
 		// Create socket pair to signal the child
@@ -60,25 +183,109 @@ impl Exec {
 		)
 		.unwrap();
 
+		// Build the arguments and environment of the target before forking, as
+		// another thread may hold the allocator lock when this process forks
+		// and the child must only call async-signal-safe functions until exec
+		let argv = exec_array(&self.args);
+		let envp = self.env.as_ref().map(|env| exec_array(env));
+
 		let mut buf = [0];
 		match fork() {
 			Ok(ForkResult::Parent { child, .. }) => {
+				let _ = close(child_sock);
 				c.socket = parent_sock;
 				c.pid = Some(child);
+
+				// A target with a timeout runs in its own process group (also
+				// set by the child, so it exists whichever runs first) and is
+				// made the foreground process group so it can use the terminal
+				if self.timeout.is_some() {
+					let _ = setpgid(child, child);
+					c.terminal = foreground(child);
+				}
+
+				// Only the child writes output and reads input
+				for fd in self.output.take().into_iter().chain(self.input.take()) {
+					let _ = close(fd);
+				}
+
+				if let Some((parent, child)) = self.control.take() {
+					let _ = close(child);
+					c.control = Some(parent);
+				}
+
+				// Write the input from a thread, as the target may not read it
+				// all before producing output (or at all) - the pipe is closed
+				// once written, so the target reads EOF.
+				if let Some((fd, buf)) = self.feed.take() {
+					thread::spawn(move || {
+						let _ = unsafe { File::from_raw_fd(fd) }.write_all(&buf);
+					});
+				}
+
+				// Read the captured output until the target (and any of its
+				// children) close the pipe
+				c.capture = self.capture.take().map(|fd| {
+					thread::spawn(move || {
+						let mut buf = vec![];
+						let _ = unsafe { File::from_raw_fd(fd) }.read_to_end(&mut buf);
+						buf
+					})
+				});
 			}
 			Ok(ForkResult::Child) => {
 				let _ = close(parent_sock);
 
-				let mut args = Vec::new();
-				for arg in self.args.iter() {
-					args.push(arg.as_c_str());
+				// Run the target in its own process group, so any processes it
+				// starts (i.e. the commands run by --shell) are signalled with
+				// it when it times out
+				if self.timeout.is_some() {
+					let _ = setpgid(Pid::from_raw(0), Pid::from_raw(0));
+				}
+
+				// The socket is closed by a successful exec, signalling the
+				// parent that the target is running
+				let _ = fcntl(child_sock, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC));
+
+				if let Some(fd) = self.output {
+					let _ = dup2(fd, 1);
+					let _ = dup2(fd, 2);
+				}
+
+				if let Some(fd) = self.input {
+					let _ = dup2(fd, 0);
+				}
+
+				// Move the control socket to CONTROL_FD, first moving the start
+				// socket out of the way if it is there, and leave it open
+				// across exec for the target
+				let mut child_sock = child_sock;
+				if let Some((_, fd)) = self.control {
+					if child_sock == CONTROL_FD {
+						child_sock = fcntl(child_sock, FcntlArg::F_DUPFD_CLOEXEC(CONTROL_FD + 1))
+							.unwrap_or(child_sock);
+					}
+					let _ = dup2(fd, CONTROL_FD);
+					let _ = fcntl(CONTROL_FD, FcntlArg::F_SETFD(FdFlag::empty()));
 				}
 
+				// Set up the process before the start signal so it doesn't
+				// count towards the measurement (and the affinity is in place
+				// before the counters start), reporting any failure in place
+				// of the exec errno
+				let err = self.setup().err();
+
 				// Wait for the "start" signal and go
 				let _ = read(child_sock, &mut buf);
-				let _ = close(child_sock);
-				let _ = execvp(&self.target, &args);
-				process::exit(BAD_EXEC);
+				let (step, err) = err.unwrap_or_else(|| {
+					(Step::Exec, execvpe(&self.target, &argv, envp.as_deref()))
+				});
+
+				let errno = err.as_errno().unwrap_or(Errno::UnknownErrno) as i32;
+				let mut msg = [step as u8; 5];
+				msg[1..].copy_from_slice(&errno.to_ne_bytes());
+				let _ = write(child_sock, &msg);
+				unsafe { libc::_exit(EXEC_FAILED) };
 			}
 			Err(_) => unreachable!("fork failed"),
 		};
@@ -86,6 +293,27 @@ impl Exec {
 		c
 	}
 
+	/// Apply the configured working directory and scheduling to the calling
+	/// (forked child) process.
+	fn setup(&self) -> Result<(), (Step, nix::Error)> {
+		if let Some(ref dir) = self.cwd {
+			chdir(dir).map_err(|e| (Step::Chdir, e))?;
+		}
+		if let Some(ref cpus) = self.cpus {
+			sched::set_affinity(cpus).map_err(|e| (Step::Affinity, e))?;
+		}
+		if let Some(nice) = self.nice {
+			sched::set_nice(nice).map_err(|e| (Step::Nice, e))?;
+		}
+		if let Some(prio) = self.rtprio {
+			sched::set_rtprio(prio).map_err(|e| (Step::RtPrio, e))?;
+		}
+		if !self.aslr {
+			layout::disable_aslr().map_err(|e| (Step::Aslr, e))?;
+		}
+		Ok(())
+	}
+
 	/// Append args to the list of arguments passed to the target process.
 	pub fn args(mut self, args: &[&str]) -> Result<Self, NulError> {
 		for arg in args.iter() {
@@ -93,6 +321,179 @@ impl Exec {
 		}
 		Ok(self)
 	}
+
+	/// Terminate the target with signal if it runs for longer than timeout
+	/// (see [`Child::run`]).
+	///
+	/// The target runs in its own process group (the foreground process group
+	/// of the terminal, if any) so that any processes it starts are also
+	/// terminated.
+	///
+	/// [`Child::run`]: struct.Child.html#method.run
+	pub fn timeout(mut self, timeout: Option<Duration>, signal: Signal) -> Self {
+		self.timeout = timeout.map(|t| (t, signal));
+		self
+	}
+
+	/// Set the environment of the target process to vars, added to the
+	/// environment of this process unless clear is true.
+	///
+	/// Vars are applied in order, so a later definition of a variable replaces
+	/// an earlier one.
+	pub fn env(mut self, clear: bool, vars: &[(String, String)]) -> Result<Self, NulError> {
+		if !clear && vars.is_empty() {
+			return Ok(self);
+		}
+
+		let mut env: Vec<(Vec<u8>, Vec<u8>)> = vec![];
+		if !clear {
+			for (k, v) in std::env::vars_os() {
+				env.push((k.as_bytes().to_vec(), v.as_bytes().to_vec()));
+			}
+		}
+
+		for (k, v) in vars {
+			env.retain(|(key, _)| key.as_slice() != k.as_bytes());
+			env.push((k.as_bytes().to_vec(), v.as_bytes().to_vec()));
+		}
+
+		let mut vars = vec![];
+		for (mut k, v) in env {
+			k.push(b'=');
+			k.extend(v);
+			vars.push(CString::new(k)?);
+		}
+
+		self.env = Some(vars);
+		Ok(self)
+	}
+
+	/// Execute the target process in dir.
+	///
+	/// A relative target path is resolved from dir, as with env(1).
+	pub fn cwd(mut self, dir: Option<&Path>) -> Self {
+		self.cwd = dir.map(Path::to_path_buf);
+		self
+	}
+
+	/// Restrict the target process to run on the given CPUs.
+	pub fn cpus(mut self, cpus: Option<&[usize]>) -> Self {
+		self.cpus = cpus.map(<[usize]>::to_vec);
+		self
+	}
+
+	/// Run the target process with the given nice value and realtime
+	/// priority (if any).
+	pub fn priority(mut self, nice: Option<i32>, rtprio: Option<i32>) -> Self {
+		self.nice = nice;
+		self.rtprio = rtprio;
+		self
+	}
+
+	/// Enable or disable address space layout randomisation for the target
+	/// process (enabled by default).
+	pub fn aslr(mut self, enabled: bool) -> Self {
+		self.aslr = enabled;
+		self
+	}
+
+	/// Redirect the stdout and stderr of the target process.
+	///
+	/// Captured output is available from [`Child::capture`].
+	///
+	/// [`Child::capture`]: struct.Child.html#method.capture
+	pub fn output(mut self, output: &Output) -> Result<Self, Error> {
+		let flags = OFlag::O_WRONLY | OFlag::O_CLOEXEC;
+		let fd = match *output {
+			Output::Inherit => return Ok(self),
+			Output::Null => open("/dev/null", flags, Mode::empty())
+				.map_err(|e| Error::ExecError(format!("failed to open /dev/null: {}", e)))?,
+			Output::File(ref path) => open(
+				path,
+				flags | OFlag::O_CREAT | OFlag::O_TRUNC,
+				Mode::from_bits_truncate(0o644),
+			)
+			.map_err(|e| Error::ExecError(format!("failed to open {}: {}", path.display(), e)))?,
+			Output::Capture => {
+				let (r, w) = pipe()
+					.map_err(|e| Error::ExecError(format!("failed to create pipe: {}", e)))?;
+				for &fd in &[r, w] {
+					let _ = fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC));
+				}
+				self.capture = Some(r);
+				w
+			}
+		};
+
+		self.output = Some(fd);
+		Ok(self)
+	}
+
+	/// Redirect the stdin of the target process.
+	pub fn input(mut self, input: &Input) -> Result<Self, Error> {
+		let fd = match *input {
+			Input::Inherit => return Ok(self),
+			Input::File(ref path) => open(path, OFlag::O_RDONLY | OFlag::O_CLOEXEC, Mode::empty())
+				.map_err(|e| Error::ExecError(format!("failed to open {}: {}", path.display(), e)))?,
+			Input::Bytes(ref buf) => {
+				let (r, w) = pipe()
+					.map_err(|e| Error::ExecError(format!("failed to create pipe: {}", e)))?;
+				for &fd in &[r, w] {
+					let _ = fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC));
+				}
+				self.feed = Some((w, buf.clone()));
+				r
+			}
+		};
+
+		self.input = Some(fd);
+		Ok(self)
+	}
+
+	/// Open a control socket to the target process at `CONTROL_FD`, over which
+	/// the target writes `+` to start and `-` to stop the counters (other
+	/// bytes are ignored), and reads `ACK` once they have been.
+	///
+	/// Markers are passed to the handler given to [`Child::run_with`].
+	///
+	/// [`Child::run_with`]: struct.Child.html#method.run_with
+	pub fn control(mut self, enabled: bool) -> Result<Self, Error> {
+		if !enabled {
+			return Ok(self);
+		}
+
+		let (parent, child) = socketpair(
+			AddressFamily::Unix,
+			SockType::Stream,
+			None,
+			SockFlag::empty(),
+		)
+		.map_err(|e| Error::ExecError(format!("failed to create control socket: {}", e)))?;
+		for &fd in &[parent, child] {
+			let _ = fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC));
+		}
+
+		self.control = Some((parent, child));
+		Ok(self)
+	}
+}
+
+impl Drop for Exec {
+	/// Close any descriptors opened for a child that was never executed.
+	fn drop(&mut self) {
+		let fds = self
+			.output
+			.take()
+			.into_iter()
+			.chain(self.capture.take())
+			.chain(self.input.take())
+			.chain(self.feed.take().map(|(fd, _)| fd))
+			.chain(self.control.take().into_iter().flat_map(|(p, c)| vec![p, c]));
+
+		for fd in fds {
+			let _ = close(fd);
+		}
+	}
 }
 
 impl Child {
@@ -100,29 +501,256 @@ impl Child {
 		self.pid.map(|pid| pid.as_raw() as u32)
 	}
 
-	pub fn run(self) -> Option<i32> {
-		self.pid?;
+	/// Take the handle of the thread capturing output (if configured), which
+	/// returns the output once the target closes its stdout and stderr.
+	pub fn capture(&mut self) -> Option<JoinHandle<Vec<u8>>> {
+		self.capture.take()
+	}
+
+	/// Signal the child to execute the target, and block until it terminates.
+	///
+	/// If a timeout is configured and the target is still running once it
+	/// elapses, the target is signalled to terminate (and killed if it has not
+	/// exited after `KILL_GRACE`) and `Status::TimedOut` is returned.
+	///
+	/// Returns [`Error::ExecFailed`] if the target could not be executed.
+	///
+	/// [`Error::ExecFailed`]: ../../error/enum.Error.html
+	pub fn run(self) -> Result<Status, Error> {
+		self.run_with(|_| Ok(()))
+	}
+
+	/// Signal the child to execute the target as with [`run`], passing each
+	/// marker read from the control socket (see [`Exec::control`]) to handler
+	/// while the target runs.
+	///
+	/// The target is acknowledged once handler returns. If handler fails, the
+	/// remaining markers are acknowledged without being handled and the error
+	/// is returned once the target terminates.
+	///
+	/// [`run`]: #method.run
+	/// [`Exec::control`]: struct.Exec.html#method.control
+	pub fn run_with<F>(mut self, mut handler: F) -> Result<Status, Error>
+	where
+		F: FnMut(Marker) -> Result<(), Error>,
+	{
+		let pid = self
+			.pid
+			.ok_or_else(|| Error::ExecError(String::from("child process not started")))?;
 
 		// Send the "start" signal to the child
 		let _ = write(self.socket, b"!");
 
-		// Block while it runs
-		match waitpid(self.pid, None) {
-			Ok(WaitStatus::Exited(_, BAD_EXEC)) => None,
-			Ok(WaitStatus::Exited(_, val)) => Some(val),
+		// Read the failed step and errno, or EOF once the target is running
+		let mut buf = [0; 5];
+		let exec_err = match read(self.socket, &mut buf) {
+			Ok(5) => {
+				let mut errno = [0; 4];
+				errno.copy_from_slice(&buf[1..]);
+				let errno = Errno::from_i32(i32::from_ne_bytes(errno));
+				Step::from_u8(buf[0]).map(|step| step.error(errno))
+			}
 			_ => None,
+		};
+
+		// Block while it runs
+		let timeout = self.timeout;
+		let reap = move || match timeout {
+			Some((timeout, signal)) => wait_timeout(pid, timeout, signal),
+			None => wait(pid),
+		};
+		let (status, handled) = match self.control {
+			None => (reap(), Ok(())),
+			Some(fd) => {
+				// Wait from a thread, shutting down the control socket once the
+				// target terminates to stop reading markers (even if a child
+				// of the target still holds it open)
+				let (tx, rx) = mpsc::channel();
+				thread::spawn(move || {
+					let status = reap();
+					let _ = shutdown(fd, Shutdown::Read);
+					tx.send(status)
+				});
+
+				let handled = markers(fd, &mut handler);
+				(rx.recv().expect("wait thread exited"), handled)
+			}
+		};
+		if let Some((tty, pgid)) = self.terminal.take() {
+			restore(tty, pgid);
+		}
+		let status = status?;
+
+		// The child has been reaped, so don't signal it (or a process that
+		// has since reused the PID) when dropped
+		self.pid = None;
+
+		match exec_err {
+			Some(err) => Err(err),
+			None => handled.map(|_| status),
+		}
+	}
+}
+
+/// Read markers from the control socket fd until it is closed or shut down,
+/// passing each to handler and acknowledging it.
+fn markers<F>(fd: RawFd, handler: &mut F) -> Result<(), Error>
+where
+	F: FnMut(Marker) -> Result<(), Error>,
+{
+	let mut result = Ok(());
+	let mut buf = [0; 64];
+	loop {
+		let n = match read(fd, &mut buf) {
+			Ok(0) => return result,
+			Ok(n) => n,
+			Err(nix::Error::Sys(Errno::EINTR)) => continue,
+			Err(_) => return result,
+		};
+
+		for &b in &buf[..n] {
+			let marker = match b {
+				b'+' => Marker::Start,
+				b'-' => Marker::Stop,
+				_ => continue,
+			};
+			if result.is_ok() {
+				result = handler(marker);
+			}
+			let _ = write(fd, ACK);
+		}
+	}
+}
+
+/// Returns the null terminated array of pointers to strings passed to exec,
+/// valid for as long as strings.
+fn exec_array(strings: &[CString]) -> Vec<*const libc::c_char> {
+	strings
+		.iter()
+		.map(|s| s.as_ptr())
+		.chain(Some(ptr::null()))
+		.collect()
+}
+
+/// Execute filename with the arguments argv and the environment envp (or the
+/// environment of this process if None), searching `PATH` for filename as with
+/// `execvp`, returning the error if it fails.
+///
+/// Both are null terminated (see [`exec_array`]) so nothing is allocated.
+///
+/// [`exec_array`]: fn.exec_array.html
+fn execvpe(
+	filename: &CStr,
+	argv: &[*const libc::c_char],
+	envp: Option<&[*const libc::c_char]>,
+) -> nix::Error {
+	match envp {
+		None => unsafe { libc::execvp(filename.as_ptr(), argv.as_ptr()) },
+		#[cfg(target_os = "linux")]
+		Some(envp) => unsafe { libc::execvpe(filename.as_ptr(), argv.as_ptr(), envp.as_ptr()) },
+		// No execvpe, so replace the environment searched and inherited by
+		// execvp - only called in the forked child, which is single threaded
+		#[cfg(not(target_os = "linux"))]
+		Some(envp) => {
+			extern "C" {
+				static mut environ: *const *const libc::c_char;
+			}
+			unsafe {
+				environ = envp.as_ptr();
+				libc::execvp(filename.as_ptr(), argv.as_ptr())
+			}
+		}
+	};
+	nix::Error::Sys(Errno::last())
+}
+
+/// Block until pid terminates, returning the exit status.
+fn wait(pid: Pid) -> Result<Status, Error> {
+	loop {
+		match waitpid(pid, None) {
+			Ok(WaitStatus::Exited(_, code)) => return Ok(Status::Exited(code)),
+			Ok(WaitStatus::Signaled(_, sig, _)) => return Ok(Status::Signaled(sig)),
+			Ok(_) | Err(nix::Error::Sys(Errno::EINTR)) => continue,
+			Err(e) => return Err(Error::ExecError(format!("failed to wait for target: {}", e))),
+		}
+	}
+}
+
+/// Block until pid terminates, or send it signal once timeout elapses followed
+/// by `SIGKILL` if it is still running after `KILL_GRACE`.
+fn wait_timeout(pid: Pid, timeout: Duration, signal: Signal) -> Result<Status, Error> {
+	let (tx, rx) = mpsc::channel();
+	thread::spawn(move || tx.send(wait(pid)));
+
+	match rx.recv_timeout(timeout) {
+		Err(RecvTimeoutError::Timeout) => (),
+		Ok(status) => return status,
+		Err(RecvTimeoutError::Disconnected) => unreachable!("wait thread exited"),
+	}
+
+	terminate(pid, signal);
+	if let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(KILL_GRACE) {
+		terminate(pid, Signal::SIGKILL);
+		let _ = rx.recv();
+	}
+
+	Ok(Status::TimedOut)
+}
+
+/// Send signal to the process group of pid - the target if it is running, and
+/// any processes it started that are still in the group.
+fn terminate(pid: Pid, signal: Signal) {
+	let _ = kill(Pid::from_raw(-pid.as_raw()), signal);
+}
+
+/// Make pgid the foreground process group of the controlling terminal if this
+/// process is in the foreground, returning the terminal and the process group
+/// to restore.
+fn foreground(pgid: Pid) -> Option<(RawFd, Pid)> {
+	let tty = open("/dev/tty", OFlag::O_RDWR | OFlag::O_CLOEXEC, Mode::empty()).ok()?;
+	match tcgetpgrp(tty) {
+		Ok(previous) if previous == getpgrp() && tcsetpgrp(tty, pgid).is_ok() => {
+			Some((tty, previous))
+		}
+		_ => {
+			let _ = close(tty);
+			None
 		}
 	}
 }
 
+/// Make pgid the foreground process group of tty again, and close it.
+fn restore(tty: RawFd, pgid: Pid) {
+	// This process is in the background until then, so block the SIGTTOU sent
+	// for changing the foreground process group
+	let mut set = SigSet::empty();
+	set.add(Signal::SIGTTOU);
+	let mut old = SigSet::empty();
+
+	let _ = pthread_sigmask(SigmaskHow::SIG_BLOCK, Some(&set), Some(&mut old));
+	let _ = tcsetpgrp(tty, pgid);
+	let _ = pthread_sigmask(SigmaskHow::SIG_SETMASK, Some(&old), None);
+	let _ = close(tty);
+}
+
 impl Drop for Child {
-	/// When a Child is dropped, the child PID is sent a `SIGTERM` signal (if
-	/// still alive).
+	/// When a Child is dropped, the child PID (or its process group, if it has
+	/// a timeout) is sent a `SIGTERM` signal (if still alive).
 	fn drop(&mut self) {
 		let _ = close(self.socket);
+		if let Some(fd) = self.control.take() {
+			let _ = close(fd);
+		}
 
 		if let Some(pid) = self.pid {
-			let _ = kill(pid, Signal::SIGTERM);
+			if self.timeout.is_some() {
+				terminate(pid, Signal::SIGTERM);
+			} else {
+				let _ = kill(pid, Signal::SIGTERM);
+			}
+		}
+		if let Some((tty, pgid)) = self.terminal.take() {
+			restore(tty, pgid);
 		}
 	}
 }
@@ -130,6 +758,7 @@ impl Drop for Child {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use std::process;
 
 	#[test]
 	fn success() {
@@ -142,7 +771,7 @@ mod tests {
 		let c = Exec::new(path).unwrap().args(&["test"]).unwrap().exec();
 
 		assert!(c.pid().is_some());
-		assert_eq!(c.run(), Some(0));
+		assert_eq!(c.run(), Ok(Status::Exited(0)));
 	}
 
 	#[test]
@@ -156,7 +785,320 @@ mod tests {
 		let c = Exec::new(path).unwrap().args(&["test"]).unwrap().exec();
 
 		assert!(c.pid().is_some());
-		assert_eq!(c.run(), Some(1));
+		assert_eq!(c.run(), Ok(Status::Exited(1)));
+	}
+
+	#[test]
+	fn signaled() {
+		let c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "kill -SEGV $$"])
+			.unwrap()
+			.exec();
+
+		assert_eq!(c.run(), Ok(Status::Signaled(Signal::SIGSEGV)));
+	}
+
+	#[test]
+	fn exit_code() {
+		// Previously indistinguishable from a failure to exec the target
+		let c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "exit 42"])
+			.unwrap()
+			.exec();
+
+		assert_eq!(c.run(), Ok(Status::Exited(42)));
+	}
+
+	#[test]
+	fn argv() {
+		// The first argument is the program name, so "$0" is printed
+		let c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "test \"$0\" = sh"])
+			.unwrap()
+			.exec();
+
+		assert_eq!(c.run(), Ok(Status::Exited(0)));
+	}
+
+	#[test]
+	fn timeout() {
+		let c = Exec::new("sleep")
+			.unwrap()
+			.args(&["10"])
+			.unwrap()
+			.timeout(Some(Duration::from_millis(10)), Signal::SIGTERM)
+			.exec();
+
+		assert_eq!(c.run(), Ok(Status::TimedOut));
+	}
+
+	#[test]
+	fn timeout_kill() {
+		// SIGTERM is ignored, so the target is killed after the grace period
+		let c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "trap '' TERM; sleep 10"])
+			.unwrap()
+			.timeout(Some(Duration::from_millis(10)), Signal::SIGTERM)
+			.exec();
+
+		assert_eq!(c.run(), Ok(Status::TimedOut));
+	}
+
+	#[test]
+	fn timeout_group() {
+		// The sleep started by the shell is terminated with it, closing the
+		// captured output
+		let mut c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "sleep 30 & wait"])
+			.unwrap()
+			.output(&Output::Capture)
+			.unwrap()
+			.timeout(Some(Duration::from_millis(10)), Signal::SIGTERM)
+			.exec();
+
+		let capture = c.capture().unwrap();
+		let start = std::time::Instant::now();
+		assert_eq!(c.run(), Ok(Status::TimedOut));
+		capture.join().unwrap();
+		assert!(start.elapsed() < Duration::from_secs(10));
+	}
+
+	#[test]
+	fn process_group() {
+		// Only a target with a timeout runs in its own process group
+		let pgid = |timeout| {
+			let mut c = Exec::new("sh")
+				.unwrap()
+				.args(&["-c", "ps -o pgid= -p $$"])
+				.unwrap()
+				.output(&Output::Capture)
+				.unwrap()
+				.timeout(timeout, Signal::SIGTERM)
+				.exec();
+
+			let pid = c.pid().unwrap();
+			let capture = c.capture().unwrap();
+			assert_eq!(c.run(), Ok(Status::Exited(0)));
+			let out = String::from_utf8(capture.join().unwrap()).unwrap();
+			(pid, out.trim().parse::<u32>().unwrap())
+		};
+
+		assert_eq!(pgid(None).1, getpgrp().as_raw() as u32);
+
+		let (pid, group) = pgid(Some(Duration::from_secs(10)));
+		assert_eq!(group, pid);
+	}
+
+	#[test]
+	fn capture() {
+		let mut c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "echo out; echo err >&2"])
+			.unwrap()
+			.output(&Output::Capture)
+			.unwrap()
+			.exec();
+
+		let capture = c.capture().unwrap();
+		assert_eq!(c.run(), Ok(Status::Exited(0)));
+		assert_eq!(capture.join().unwrap(), b"out\nerr\n");
+	}
+
+	#[test]
+	fn output_file() {
+		let path = std::env::temp_dir().join(format!("benchpmc-exec-{}", process::id()));
+
+		let c = Exec::new("echo")
+			.unwrap()
+			.args(&["hello"])
+			.unwrap()
+			.output(&Output::File(path.clone()))
+			.unwrap()
+			.exec();
+
+		assert_eq!(c.run(), Ok(Status::Exited(0)));
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello\n");
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn input_bytes() {
+		let c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "test \"$(cat)\" = hello"])
+			.unwrap()
+			.input(&Input::Bytes(b"hello".to_vec()))
+			.unwrap()
+			.exec();
+
+		assert_eq!(c.run(), Ok(Status::Exited(0)));
+	}
+
+	#[test]
+	fn input_file() {
+		let path = std::env::temp_dir().join(format!("benchpmc-exec-input-{}", process::id()));
+		std::fs::write(&path, "hello").unwrap();
+
+		// Each execution reads the file from the start
+		for _ in 0..2 {
+			let c = Exec::new("sh")
+				.unwrap()
+				.args(&["-c", "test \"$(cat)\" = hello"])
+				.unwrap()
+				.input(&Input::File(path.clone()))
+				.unwrap()
+				.exec();
+
+			assert_eq!(c.run(), Ok(Status::Exited(0)));
+		}
+
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn env() {
+		let vars = vec![
+			(String::from("BENCHPMC_A"), String::from("1")),
+			(String::from("BENCHPMC_A"), String::from("2")),
+		];
+
+		let c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "test \"$BENCHPMC_A\" = 2 && test -n \"$PATH\""])
+			.unwrap()
+			.env(false, &vars)
+			.unwrap()
+			.exec();
+
+		assert_eq!(c.run(), Ok(Status::Exited(0)));
+	}
+
+	#[test]
+	fn env_clear() {
+		let vars = vec![(String::from("BENCHPMC_A"), String::from("1"))];
+
+		let mut c = Exec::new("/usr/bin/env")
+			.unwrap()
+			.output(&Output::Capture)
+			.unwrap()
+			.env(true, &vars)
+			.unwrap()
+			.exec();
+
+		let capture = c.capture().unwrap();
+		assert_eq!(c.run(), Ok(Status::Exited(0)));
+		assert_eq!(capture.join().unwrap(), b"BENCHPMC_A=1\n");
+	}
+
+	#[test]
+	fn cwd() {
+		let c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "test \"$(pwd)\" = /"])
+			.unwrap()
+			.cwd(Some(Path::new("/")))
+			.exec();
+
+		assert_eq!(c.run(), Ok(Status::Exited(0)));
+	}
+
+	#[test]
+	#[cfg(target_os = "linux")]
+	fn cpus() {
+		let c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "grep -q '^Cpus_allowed_list:\\s*0$' /proc/self/status"])
+			.unwrap()
+			.cpus(Some(&[0]))
+			.exec();
+
+		assert_eq!(c.run(), Ok(Status::Exited(0)));
+	}
+
+	#[test]
+	fn nice() {
+		let c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "test \"$(nice)\" -ge 5"])
+			.unwrap()
+			.priority(Some(5), None)
+			.exec();
+
+		assert_eq!(c.run(), Ok(Status::Exited(0)));
+	}
+
+	#[test]
+	#[cfg(target_os = "linux")]
+	fn aslr() {
+		// The mappings of the target are the same on every run
+		let maps = || {
+			let mut c = Exec::new("cat")
+				.unwrap()
+				.args(&["/proc/self/maps"])
+				.unwrap()
+				.output(&Output::Capture)
+				.unwrap()
+				.aslr(false)
+				.exec();
+
+			let capture = c.capture().unwrap();
+			assert_eq!(c.run(), Ok(Status::Exited(0)));
+			capture.join().unwrap()
+		};
+
+		assert_eq!(maps(), maps());
+	}
+
+	#[test]
+	fn control() {
+		let c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "printf + >&3 && read ack <&3 && printf '\\n-' >&3 && read ack <&3"])
+			.unwrap()
+			.control(true)
+			.unwrap()
+			.exec();
+
+		let mut markers = vec![];
+		let status = c.run_with(|m| {
+			markers.push(m);
+			Ok(())
+		});
+		assert_eq!(status, Ok(Status::Exited(0)));
+		assert_eq!(markers, vec![Marker::Start, Marker::Stop]);
+	}
+
+	#[test]
+	fn control_err() {
+		// The target is still acknowledged, and the error returned once it
+		// exits
+		let c = Exec::new("sh")
+			.unwrap()
+			.args(&["-c", "printf + >&3 && read ack <&3 && printf - >&3 && read ack <&3"])
+			.unwrap()
+			.control(true)
+			.unwrap()
+			.exec();
+
+		assert_eq!(c.run_with(|_| Err(Error::MockError)), Err(Error::MockError));
+	}
+
+	#[test]
+	fn bad_cwd() {
+		let c = Exec::new("sh")
+			.unwrap()
+			.cwd(Some(Path::new("/not-a-thing")))
+			.exec();
+
+		assert_eq!(
+			c.run(),
+			Err(Error::SetupFailed("change directory", Errno::ENOENT))
+		);
 	}
 
 	#[test]
@@ -168,6 +1110,6 @@ mod tests {
 			.exec();
 
 		assert!(c.pid().is_some());
-		assert_eq!(c.run(), None);
+		assert_eq!(c.run(), Err(Error::ExecFailed(Errno::ENOENT)));
 	}
 }
@@ -0,0 +1,66 @@
+//! Control of the memory layout of the target process, applied by the forked
+//! child before it executes the target.
+
+use nix::errno::Errno;
+use nix::libc;
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Disable address space layout randomisation for the calling process and the
+/// target it executes.
+#[cfg(target_os = "linux")]
+pub fn disable_aslr() -> nix::Result<()> {
+	// Not exposed by the libc crate - see personality(2)
+	const ADDR_NO_RANDOMIZE: libc::c_ulong = 0x0040000;
+	const QUERY: libc::c_ulong = 0xffff_ffff;
+
+	let persona = Errno::result(unsafe { libc::personality(QUERY) })?;
+	let ret = unsafe { libc::personality(persona as libc::c_ulong | ADDR_NO_RANDOMIZE) };
+	Errno::result(ret).map(drop)
+}
+
+/// Disable address space layout randomisation for the calling process and the
+/// target it executes.
+#[cfg(target_os = "freebsd")]
+pub fn disable_aslr() -> nix::Result<()> {
+	// The procctl(2) interface is not exposed by the libc crate
+	const P_PID: libc::c_int = 0;
+	const PROC_ASLR_CTL: libc::c_int = 13;
+	const PROC_ASLR_FORCE_DISABLE: libc::c_int = 2;
+
+	extern "C" {
+		fn procctl(
+			idtype: libc::c_int,
+			id: libc::id_t,
+			cmd: libc::c_int,
+			data: *mut libc::c_void,
+		) -> libc::c_int;
+	}
+
+	let mut arg = PROC_ASLR_FORCE_DISABLE;
+	let ret = unsafe {
+		procctl(
+			P_PID,
+			libc::getpid() as libc::id_t,
+			PROC_ASLR_CTL,
+			&mut arg as *mut libc::c_int as *mut libc::c_void,
+		)
+	};
+	Errno::result(ret).map(drop)
+}
+
+#[cfg(not(any(target_os = "freebsd", target_os = "linux")))]
+pub fn disable_aslr() -> nix::Result<()> {
+	Err(nix::Error::Sys(Errno::ENOSYS))
+}
+
+/// Returns a random number of bytes in `0..max` to pad the environment of the
+/// target with - the environment is copied onto the stack of the target, so
+/// this shifts the alignment of its stack.
+pub fn env_padding(max: usize) -> usize {
+	// Each RandomState is seeded differently, which is plenty random enough
+	// to vary the layout between runs
+	let v = RandomState::new().build_hasher().finish();
+	(v % max as u64) as usize
+}
@@ -1,6 +1,32 @@
 mod exec;
+mod layout;
+mod sched;
 
 use error::Error;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::fmt;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// `SHELL` is the shell used to execute commands (see [`shell`]).
+///
+/// [`shell`]: fn.shell.html
+pub const SHELL: &str = "/bin/sh";
+
+/// `ENV_PAD` is the environment variable used to pad the environment of the
+/// target when [`Options::random_env`] is set, by up to `ENV_PAD_MAX` bytes.
+///
+/// [`Options::random_env`]: struct.Options.html#structfield.random_env
+const ENV_PAD: &str = "BENCHPMC_ENV_PAD";
+const ENV_PAD_MAX: usize = 4096;
+
+/// `CONTROL_ENV` is the environment variable set to the descriptor of the
+/// control socket in the target when [`Options::markers`] is set.
+///
+/// [`Options::markers`]: struct.Options.html#structfield.markers
+pub const CONTROL_ENV: &str = "BENCHPMC_CONTROL_FD";
 
 /// Counter abstracts an implementation of a process-attachable counter.
 pub trait Counter {
@@ -10,17 +36,170 @@ pub trait Counter {
 	fn set(&mut self, value: u64) -> Result<u64, Error>;
 }
 
+/// `Status` describes how an execution of the target terminated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+	/// The target exited with the given exit code.
+	Exited(i32),
+	/// The target was terminated by the given signal.
+	Signaled(Signal),
+	/// The target was terminated for exceeding the configured timeout.
+	TimedOut,
+}
+
+impl Status {
+	/// Returns true if the target exited with an exit code of 0.
+	pub fn success(self) -> bool {
+		self == Status::Exited(0)
+	}
+}
+
+impl fmt::Display for Status {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Status::Exited(code) => write!(f, "exited with status {}", code),
+			Status::Signaled(sig) => write!(f, "killed by {}", sig.as_str()),
+			Status::TimedOut => write!(f, "timed out"),
+		}
+	}
+}
+
+impl From<Status> for Error {
+	fn from(status: Status) -> Self {
+		match status {
+			Status::Exited(code) => Error::ExitStatus(code),
+			Status::Signaled(sig) => Error::Signaled(sig),
+			Status::TimedOut => Error::TimedOut,
+		}
+	}
+}
+
+/// `Marker` is a request from the target to start or stop counting, delimiting
+/// the region of interest of the target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Marker {
+	Start,
+	Stop,
+}
+
+/// `Output` is the destination of the stdout and stderr of the target.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+	/// Share the stdout and stderr of benchpmc.
+	Inherit,
+	/// Discard all output.
+	Null,
+	/// Write all output to the file at the given path, replacing any existing
+	/// file.
+	File(PathBuf),
+	/// Capture all output in memory (see [`Runner::output`]).
+	///
+	/// [`Runner::output`]: struct.Runner.html#method.output
+	Capture,
+}
+
+/// `Input` is the source of the stdin of the target.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+	/// Share the stdin of benchpmc.
+	Inherit,
+	/// Read from the file at the given path, opened again for every run so
+	/// each run reads the same input.
+	File(PathBuf),
+	/// Read the given bytes, piped to the target on every run.
+	Bytes(Vec<u8>),
+}
+
+/// `Options` configures the execution of the target by a [`Runner`].
+///
+/// [`Runner`]: struct.Runner.html
+#[derive(Debug, Clone)]
+pub struct Options {
+	/// Terminate the target if it is still running after this long.
+	pub timeout: Option<Duration>,
+
+	/// The signal sent to terminate the target on timeout - if it is still
+	/// running after a grace period, it is sent `SIGKILL`.
+	pub timeout_signal: Signal,
+
+	/// Where the stdout and stderr of the target are sent.
+	pub output: Output,
+
+	/// Where the stdin of the target is read from.
+	pub input: Input,
+
+	/// Variables set in the environment of the target, in order.
+	pub env: Vec<(String, String)>,
+	/// Start the target with an empty environment (other than env).
+	pub env_clear: bool,
+	/// The working directory of the target, or None to inherit it.
+	pub cwd: Option<PathBuf>,
+
+	/// The CPUs the target is restricted to, or None for any CPU.
+	pub cpus: Option<Vec<usize>>,
+	/// The nice value of the target, or None to inherit it.
+	pub nice: Option<i32>,
+	/// The realtime priority of the target, or None to use the default
+	/// scheduling policy.
+	pub rtprio: Option<i32>,
+
+	/// Run the target with address space layout randomisation disabled.
+	pub disable_aslr: bool,
+	/// Pad the environment of the target by a random amount on every run,
+	/// randomising the alignment of its stack.
+	pub random_env: bool,
+
+	/// Count events only between the start and stop markers sent by the
+	/// target over the control socket (see [`CONTROL_ENV`]), rather than for
+	/// the whole lifetime of the target.
+	///
+	/// [`CONTROL_ENV`]: constant.CONTROL_ENV.html
+	pub markers: bool,
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Options {
+			timeout: None,
+			timeout_signal: Signal::SIGTERM,
+			output: Output::Inherit,
+			input: Input::Inherit,
+			env: Vec::new(),
+			env_clear: false,
+			cwd: None,
+			cpus: None,
+			nice: None,
+			rtprio: None,
+			disable_aslr: false,
+			random_env: false,
+			markers: false,
+		}
+	}
+}
+
 /// Runner executes a given target process, attaches the provided counters and
 /// runs them for the duration of target execution.
 pub struct Runner<'a> {
 	target: &'a str,
 	args: Option<&'a [&'a str]>,
+	options: Options,
+	output: Vec<u8>,
 }
 
 impl<'a> Runner<'a> {
 	/// New creates a new Runner that executes target.
 	pub fn new(target: &'a str) -> Self {
-		Runner { target, args: None }
+		Runner {
+			target,
+			args: None,
+			options: Options::default(),
+			output: Vec::new(),
+		}
+	}
+
+	/// Specifies how the target process is executed.
+	pub fn options(self, options: Options) -> Self {
+		Runner { options, ..self }
 	}
 
 	/// Specifies arguments to the target process.
@@ -31,12 +210,47 @@ impl<'a> Runner<'a> {
 		}
 	}
 
+	/// Returns the output of the target captured by the last call to run, if
+	/// configured to capture it.
+	pub fn output(&self) -> &[u8] {
+		&self.output
+	}
+
 	/// Run starts the execution of the configured target, attaching events to
 	/// the child process.
-	pub fn run<T: Counter + ?Sized>(&mut self, events: &mut [Box<T>]) -> Result<(), Error> {
-		let child = exec::Exec::new(self.target)?
+	///
+	/// The counters are read regardless of how the target terminates, and the
+	/// exit status is returned for the caller to act upon.
+	///
+	/// If [`Options::markers`] is set, the counters are started and stopped by
+	/// the markers sent by the target instead, and are left at zero if it
+	/// sends none.
+	///
+	/// [`Options::markers`]: struct.Options.html#structfield.markers
+	pub fn run<T: Counter + ?Sized>(&mut self, events: &mut [Box<T>]) -> Result<Status, Error> {
+		let mut env = self.options.env.clone();
+		if self.options.random_env {
+			let pad = layout::env_padding(ENV_PAD_MAX);
+			env.push((String::from(ENV_PAD), "x".repeat(pad)));
+		}
+		let markers = self.options.markers;
+		if markers {
+			env.push((String::from(CONTROL_ENV), exec::CONTROL_FD.to_string()));
+		}
+
+		let mut child = exec::Exec::new(self.target)?
 			.args(self.args.unwrap_or(&[]))?
+			.timeout(self.options.timeout, self.options.timeout_signal)
+			.output(&self.options.output)?
+			.input(&self.options.input)?
+			.env(self.options.env_clear, &env)?
+			.cwd(self.options.cwd.as_deref())
+			.cpus(self.options.cpus.as_deref())
+			.priority(self.options.nice, self.options.rtprio)
+			.aslr(!self.options.disable_aslr)
+			.control(markers)?
 			.exec();
+		let capture = child.capture();
 
 		let pid = child
 			.pid()
@@ -45,39 +259,153 @@ impl<'a> Runner<'a> {
 		// Attach counters to the child process in one go, then start running
 		// them to have the start time delta as low as possible.
 		for counter in events.iter_mut() {
-			counter.attach(pid)?;
+			counter.attach(pid).map_err(counter_error("attach"))?;
 		}
 
-		for counter in events.iter_mut() {
-			counter.start().unwrap();
+		let mut running = !markers;
+		if running {
+			for counter in events.iter_mut() {
+				counter.start().map_err(counter_error("start"))?;
+			}
 		}
 
-		// Signal the child to start and check it's return value
-		match child.run() {
-			Some(0) => Ok(()),
-			Some(_) => Err("non-zero exit status"),
-			None => Err("failed to exec"),
+		// Signal the child to start and wait for it to terminate, counting
+		// between the markers it sends (if configured)
+		let status = child.run_with(|marker| {
+			match marker {
+				Marker::Start if !running => {
+					for counter in events.iter_mut() {
+						counter.start().map_err(counter_error("start"))?;
+					}
+				}
+				Marker::Stop if running => {
+					for counter in events.iter_mut() {
+						counter.stop().map_err(counter_error("stop"))?;
+					}
+				}
+				_ => return Ok(()),
+			}
+			running = marker == Marker::Start;
+			Ok(())
+		})?;
+
+		// A target killed on timeout may have left behind children holding
+		// the output open, so don't wait for them
+		if let Some(capture) = capture {
+			if status != Status::TimedOut {
+				self.output = capture.join().unwrap_or_default();
+			}
 		}
-		.map_err(|e| Error::ExecError(e.to_string()))?;
 
 		// Stop all counters and reset them
+		if running {
+			for counter in events.iter_mut() {
+				counter.stop().map_err(counter_error("stop"))?;
+			}
+		}
+
 		for counter in events.iter_mut() {
-			counter.stop().unwrap();
+			counter.set(0).map_err(counter_error("read"))?;
+		}
+
+		Ok(status)
+	}
+}
+
+/// `Attach` measures an already running process (one not started by benchpmc)
+/// for a fixed interval, in place of a [`Runner`].
+///
+/// [`Runner`]: struct.Runner.html
+pub struct Attach {
+	pid: u32,
+	duration: Duration,
+	attached: bool,
+}
+
+impl Attach {
+	/// New creates an Attach measuring the process pid for intervals of
+	/// duration.
+	pub fn new(pid: u32, duration: Duration) -> Self {
+		Attach {
+			pid,
+			duration,
+			attached: false,
+		}
+	}
+
+	/// Run measures a single interval, attaching the counters to the process
+	/// on the first call.
+	///
+	/// The process keeps running after the interval, so a measured interval
+	/// always has a successful status - an error is returned if the process
+	/// terminated before the end of the interval.
+	pub fn run<T: Counter + ?Sized>(&mut self, events: &mut [Box<T>]) -> Result<Status, Error> {
+		if !self.attached {
+			for counter in events.iter_mut() {
+				counter.attach(self.pid).map_err(counter_error("attach"))?;
+			}
+			self.attached = true;
 		}
 
 		for counter in events.iter_mut() {
-			counter.set(0)?;
+			counter.start().map_err(counter_error("start"))?;
 		}
 
-		Ok(())
+		thread::sleep(self.duration);
+
+		for counter in events.iter_mut() {
+			counter.stop().map_err(counter_error("stop"))?;
+		}
+
+		for counter in events.iter_mut() {
+			counter.set(0).map_err(counter_error("read"))?;
+		}
+
+		// Signal 0 only checks the process still exists
+		if kill(Pid::from_raw(self.pid as i32), None).is_err() {
+			return Err(Error::ExecError(format!(
+				"process {} terminated during the interval",
+				self.pid
+			)));
+		}
+
+		Ok(Status::Exited(0))
 	}
 }
 
+/// Execute cmd with sh(1) to completion without attaching counters (i.e. a
+/// command run around the target), in the environment and working directory
+/// of the target.
+///
+/// The output of cmd is discarded unless the output of the target is
+/// inherited.
+pub fn shell(cmd: &str, options: &Options) -> Result<Status, Error> {
+	let output = match options.output {
+		Output::Inherit => Output::Inherit,
+		_ => Output::Null,
+	};
+
+	exec::Exec::new(SHELL)?
+		.args(&["-c", cmd])?
+		.env(options.env_clear, &options.env)?
+		.cwd(options.cwd.as_deref())
+		.output(&output)?
+		.exec()
+		.run()
+}
+
+/// Returns a function wrapping a counter error with the failed operation.
+fn counter_error(op: &'static str) -> impl Fn(Error) -> Error {
+	move |e| Error::CounterError(op, Box::new(e))
+}
+
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
 	mod mock_event;
 
 	use super::*;
+	use nix::errno::Errno;
 
 	#[test]
 	fn success() {
@@ -91,7 +419,7 @@ mod tests {
 
 		let mut counters = vec![Box::new(mock_event::new())];
 
-		assert!(r.run(&mut counters).is_ok());
+		assert_eq!(r.run(&mut counters), Ok(Status::Exited(0)));
 	}
 
 	#[test]
@@ -121,19 +449,99 @@ mod tests {
 
 		let mut r = Runner::new(path);
 
+		// The counters are still read, and the status left to the caller
+		let counters = &mut [Box::new(mock_event::new())];
+		assert_eq!(r.run(counters), Ok(Status::Exited(1)));
+		assert_eq!(counters[0].value, Some(0));
+	}
+
+	#[test]
+	fn timeout() {
+		let mut r = Runner::new("sleep").args(&["10"]).options(Options {
+			timeout: Some(Duration::from_millis(10)),
+			..Options::default()
+		});
+
+		let counters = &mut [Box::new(mock_event::new())];
+		assert_eq!(r.run(counters), Ok(Status::TimedOut));
+		assert_eq!(counters[0].value, Some(0));
+	}
+
+	#[test]
+	fn random_env() {
+		let mut r = Runner::new("sh")
+			.args(&["-c", "test -n \"${BENCHPMC_ENV_PAD+set}\" && test ${#BENCHPMC_ENV_PAD} -lt 4096"])
+			.options(Options {
+				random_env: true,
+				..Options::default()
+			});
+
+		let counters = &mut [Box::new(mock_event::new())];
+		assert_eq!(r.run(counters), Ok(Status::Exited(0)));
+	}
+
+	#[test]
+	fn test_shell() {
+		let options = Options {
+			env: vec![(String::from("BENCHPMC_A"), String::from("1"))],
+			..Options::default()
+		};
+
+		assert_eq!(shell("test \"$BENCHPMC_A\" = 1", &options), Ok(Status::Exited(0)));
+		assert_eq!(shell("exit 3", &options), Ok(Status::Exited(3)));
+	}
+
+	#[test]
+	fn markers() {
+		let options = Options {
+			markers: true,
+			..Options::default()
+		};
+
+		// The counters are only started by a start marker
+		let mut err = mock_event::new();
+		err.start_err = Some(Error::MockError);
+		let counters = &mut [Box::new(err)];
+
+		let mut r = Runner::new("sh")
+			.args(&["-c", "test \"$BENCHPMC_CONTROL_FD\" = 3"])
+			.options(options.clone());
+		assert_eq!(r.run(counters), Ok(Status::Exited(0)));
+
+		let mut r = Runner::new("sh")
+			.args(&["-c", "printf + >&3 && read ack <&3"])
+			.options(options);
 		assert_eq!(
-			r.run(&mut vec![Box::new(mock_event::new())]).unwrap_err(),
-			Error::ExecError(String::from("non-zero exit status"))
+			r.run(counters),
+			Err(Error::CounterError("start", Box::new(Error::MockError)))
 		);
 	}
 
+	#[test]
+	fn attach() {
+		let mut child = std::process::Command::new("sleep").arg("10").spawn().unwrap();
+		let mut a = Attach::new(child.id(), Duration::from_millis(10));
+
+		let counters = &mut [Box::new(mock_event::new())];
+		assert_eq!(a.run(counters), Ok(Status::Exited(0)));
+		assert_eq!(counters[0].value, Some(0));
+
+		// The counters are attached once, for every interval
+		counters[0].attach_err = Some(Error::MockError);
+		assert_eq!(a.run(counters), Ok(Status::Exited(0)));
+
+		child.kill().unwrap();
+		child.wait().unwrap();
+		assert!(a.run(counters).is_err());
+	}
+
 	#[test]
 	fn bad_exec() {
 		let mut r = Runner::new("not-a-thing");
 
 		assert_eq!(
 			r.run(&mut vec![Box::new(mock_event::new())]).unwrap_err(),
-			Error::ExecError(String::from("failed to exec"))
+			Error::ExecFailed(Errno::ENOENT)
 		);
 	}
 
@@ -155,11 +563,13 @@ mod tests {
 		let path = "/bin/true";
 
 		let mut r = Runner::new(path);
-		assert_eq!(r.run(counters), Err(Error::MockError));
+		assert_eq!(
+			r.run(counters),
+			Err(Error::CounterError("attach", Box::new(Error::MockError)))
+		);
 	}
 
 	#[test]
-	#[should_panic]
 	fn start_err() {
 		let mut err = mock_event::new();
 		err.start_err = Some(Error::MockError);
@@ -176,11 +586,13 @@ mod tests {
 		#[cfg(not(any(target_os = "freebsd", target_os = "macos")))]
 		let path = "/bin/true";
 
-		let _ = Runner::new(path).run(counters);
+		assert_eq!(
+			Runner::new(path).run(counters),
+			Err(Error::CounterError("start", Box::new(Error::MockError)))
+		);
 	}
 
 	#[test]
-	#[should_panic]
 	fn stop_err() {
 		let mut err = mock_event::new();
 		err.stop_err = Some(Error::MockError);
@@ -197,7 +609,10 @@ mod tests {
 		#[cfg(not(any(target_os = "freebsd", target_os = "macos")))]
 		let path = "/bin/true";
 
-		let _ = Runner::new(path).run(counters);
+		assert_eq!(
+			Runner::new(path).run(counters),
+			Err(Error::CounterError("stop", Box::new(Error::MockError)))
+		);
 	}
 
 	#[test]
@@ -215,7 +630,10 @@ mod tests {
 
 		let mut r = Runner::new(path);
 
-		assert_eq!(r.run(counters), Err(Error::MockError));
+		assert_eq!(
+			r.run(counters),
+			Err(Error::CounterError("read", Box::new(Error::MockError)))
+		);
 		assert_eq!(counters[0].value, Some(0));
 	}
 
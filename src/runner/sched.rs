@@ -0,0 +1,98 @@
+//! Scheduling of the target process, applied by the forked child before it
+//! executes the target.
+
+use nix::errno::Errno;
+use nix::libc;
+
+/// Restrict the calling process to run on cpus only.
+#[cfg(target_os = "linux")]
+pub fn set_affinity(cpus: &[usize]) -> nix::Result<()> {
+	use nix::sched::{sched_setaffinity, CpuSet};
+	use nix::unistd::Pid;
+
+	let mut set = CpuSet::new();
+	for &cpu in cpus {
+		set.set(cpu)?;
+	}
+
+	sched_setaffinity(Pid::from_raw(0), &set)
+}
+
+/// Restrict the calling process to run on cpus only.
+#[cfg(target_os = "freebsd")]
+pub fn set_affinity(cpus: &[usize]) -> nix::Result<()> {
+	// The cpuset(2) interface is not exposed by the libc crate
+	const CPU_LEVEL_WHICH: libc::c_int = 3;
+	const CPU_WHICH_PID: libc::c_int = 2;
+	const CPU_SETSIZE: usize = 256;
+
+	extern "C" {
+		fn cpuset_setaffinity(
+			level: libc::c_int,
+			which: libc::c_int,
+			id: libc::id_t,
+			setsize: libc::size_t,
+			mask: *const u64,
+		) -> libc::c_int;
+	}
+
+	let mut set = [0u64; CPU_SETSIZE / 64];
+	for &cpu in cpus {
+		if cpu >= CPU_SETSIZE {
+			return Err(nix::Error::Sys(Errno::EINVAL));
+		}
+		set[cpu / 64] |= 1 << (cpu % 64);
+	}
+
+	let ret = unsafe {
+		cpuset_setaffinity(
+			CPU_LEVEL_WHICH,
+			CPU_WHICH_PID,
+			-1,
+			std::mem::size_of_val(&set),
+			set.as_ptr(),
+		)
+	};
+	Errno::result(ret).map(drop)
+}
+
+#[cfg(not(any(target_os = "freebsd", target_os = "linux")))]
+pub fn set_affinity(_cpus: &[usize]) -> nix::Result<()> {
+	Err(nix::Error::Sys(Errno::ENOSYS))
+}
+
+/// Set the nice value of the calling process.
+pub fn set_nice(nice: i32) -> nix::Result<()> {
+	let ret = unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) };
+	Errno::result(ret).map(drop)
+}
+
+/// Schedule the calling process with the given realtime (FIFO) priority.
+#[cfg(target_os = "linux")]
+pub fn set_rtprio(prio: i32) -> nix::Result<()> {
+	let param = libc::sched_param {
+		sched_priority: prio,
+	};
+	let ret = unsafe { libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) };
+	Errno::result(ret).map(drop)
+}
+
+/// Schedule the calling process with the given realtime priority (see
+/// rtprio(1)).
+#[cfg(target_os = "freebsd")]
+pub fn set_rtprio(prio: i32) -> nix::Result<()> {
+	let mut rtp = libc::rtprio {
+		type_: libc::RTP_PRIO_REALTIME,
+		prio: prio as libc::c_ushort,
+	};
+
+	// The forked child is single threaded, so setting the priority of the
+	// calling thread sets it for the process
+	let ret = unsafe { libc::rtprio_thread(libc::RTP_SET, 0, &mut rtp) };
+	Errno::result(ret).map(drop)
+}
+
+#[cfg(not(any(target_os = "freebsd", target_os = "linux")))]
+pub fn set_rtprio(_prio: i32) -> nix::Result<()> {
+	Err(nix::Error::Sys(Errno::ENOSYS))
+}
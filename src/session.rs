@@ -0,0 +1,692 @@
+use baseline::Baseline;
+use error::Error;
+use event::Sampled;
+use report::{Report, Run};
+use runner::{self, Attach, Counter, Options, Output, Runner, Status};
+use stats;
+
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// `DisplayCounter` composes the traits required to run, display and export a
+/// counter
+pub trait DisplayCounter: Counter + Display + Sampled {}
+impl<T: Counter + Display + Sampled> DisplayCounter for T {}
+
+/// `Policy` determines how a run of the target that fails is handled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Policy {
+	/// Abort the session with an error.
+	Abort,
+	/// Record the run and include its values in the results.
+	Ignore,
+	/// Record the run, but exclude its values from the results.
+	Exclude,
+}
+
+impl FromStr for Policy {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"abort" => Ok(Policy::Abort),
+			"ignore" => Ok(Policy::Ignore),
+			"exclude" => Ok(Policy::Exclude),
+			_ => Err(format!("unknown policy '{}'", s)),
+		}
+	}
+}
+
+/// `Hooks` are shell commands run around the target, without counters attached.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hooks<'a> {
+	/// Run once before the first run of the target (including warmups).
+	pub setup: Option<&'a str>,
+	/// Run before every run of the target.
+	pub prepare: Option<&'a str>,
+	/// Run after every run of the target.
+	pub conclude: Option<&'a str>,
+	/// Run once after the last run of the target.
+	pub cleanup: Option<&'a str>,
+}
+
+/// `Session` is a target command line, the counters measuring it, and the
+/// runs of it observed so far.
+pub struct Session<'a> {
+	target: &'a str,
+	args: Vec<&'a str>,
+	counters: Vec<Box<dyn DisplayCounter + 'a>>,
+	runs: Vec<Run>,
+	options: Options,
+	on_failure: Policy,
+	on_timeout: Policy,
+	hooks: Hooks<'a>,
+	/// The failure of a hook run around the last run, if any.
+	hook_error: Option<Error>,
+	/// The value subtracted from each event every run (see
+	/// [`calibrate`](#method.calibrate)).
+	overhead: Vec<(String, u64)>,
+	/// The value of each event (in the order of the samples) observed in each
+	/// included run before the overhead was subtracted - empty unless
+	/// calibrated.
+	raw: Vec<Vec<u64>>,
+	/// The running process measured in place of executing the target, if set.
+	attach: Option<Attach>,
+
+	/// Directory the output of each run is written to, if set.
+	output_dir: Option<PathBuf>,
+	/// Captured output of the last run, if it failed.
+	output: Vec<u8>,
+	warmups: usize,
+}
+
+impl<'a> Session<'a> {
+	pub fn new(target: &'a str, args: Vec<&'a str>, counters: Vec<Box<dyn DisplayCounter + 'a>>) -> Self {
+		Session {
+			target,
+			args,
+			counters,
+			runs: Vec::new(),
+			options: Options::default(),
+			on_failure: Policy::Abort,
+			on_timeout: Policy::Abort,
+			hooks: Hooks::default(),
+			hook_error: None,
+			overhead: Vec::new(),
+			raw: Vec::new(),
+			attach: None,
+			output_dir: None,
+			output: Vec::new(),
+			warmups: 0,
+		}
+	}
+
+	/// Write the stdout and stderr of each run to a file in dir, named after
+	/// the run (i.e. `run-1.log`, or `warmup-1.log` for warmup runs).
+	pub fn output_dir(mut self, dir: PathBuf) -> Self {
+		self.output_dir = Some(dir);
+		self
+	}
+
+	/// Measure the running process pid for an interval of duration every run,
+	/// instead of executing the target.
+	pub fn attach(mut self, pid: u32, duration: Duration) -> Self {
+		self.attach = Some(Attach::new(pid, duration));
+		self
+	}
+
+	/// Set the options used to execute the target.
+	pub fn options(mut self, options: Options) -> Self {
+		self.options = options;
+		self
+	}
+
+	/// Set the policy applied to runs of the target exiting with a non-zero
+	/// exit status, or terminated by a signal.
+	pub fn on_failure(mut self, policy: Policy) -> Self {
+		self.on_failure = policy;
+		self
+	}
+
+	/// Set the policy applied to runs of the target exceeding the timeout.
+	pub fn on_timeout(mut self, policy: Policy) -> Self {
+		self.on_timeout = policy;
+		self
+	}
+
+	/// Set the commands run around the target.
+	///
+	/// A failed hook is handled according to the failure policy - a run is
+	/// excluded if either of the "prepare" or "conclude" commands run around it
+	/// fail.
+	pub fn hooks(mut self, hooks: Hooks<'a>) -> Self {
+		self.hooks = hooks;
+		self
+	}
+
+	/// Returns the policy applied to a run terminating with status.
+	fn policy(&self, status: Status) -> Policy {
+		match status {
+			Status::TimedOut => self.on_timeout,
+			_ => self.on_failure,
+		}
+	}
+
+	pub fn target(&self) -> &'a str {
+		self.target
+	}
+
+	pub fn args(&self) -> &[&'a str] {
+		&self.args
+	}
+
+	pub fn counters(&self) -> &[Box<dyn DisplayCounter + 'a>] {
+		&self.counters
+	}
+
+	/// Execute the target once, recording the counter values, runtime and exit
+	/// status.
+	///
+	/// A failed (or timed out) run is handled according to the configured
+	/// [`Policy`].
+	///
+	/// [`Policy`]: enum.Policy.html
+	pub fn run(&mut self) -> Result<&Run, Error> {
+		let name = format!("run-{}", self.runs.len() + 1);
+		let (runtime, status) = self.exec(&name)?;
+
+		let mut run = Run {
+			runtime,
+			status,
+			excluded: false,
+		};
+
+		let policy = if !status.success() {
+			self.policy(status)
+		} else if self.hook_error.is_some() {
+			self.on_failure
+		} else {
+			Policy::Ignore
+		};
+
+		let value = self.included();
+		match policy {
+			Policy::Abort => return Err(status.into()),
+			Policy::Ignore => (),
+			Policy::Exclude => {
+				for c in &mut self.counters {
+					c.exclude(value);
+				}
+				run.excluded = true;
+			}
+		}
+
+		if !run.excluded && !self.overhead.is_empty() {
+			let raw = self
+				.counters
+				.iter()
+				.flat_map(|c| c.samples())
+				.map(|s| s.values[value])
+				.collect();
+			self.raw.push(raw);
+
+			for (event, overhead) in &self.overhead {
+				for c in &mut self.counters {
+					c.subtract(value, event, *overhead);
+				}
+			}
+		}
+
+		self.runs.push(run);
+		Ok(self.runs.last().unwrap())
+	}
+
+	/// Returns the output captured from the last run if it failed (and the
+	/// output is configured to be captured), or an empty slice.
+	pub fn output(&self) -> &[u8] {
+		&self.output
+	}
+
+	/// Returns the failure of the last hook run (or of those run around the
+	/// last run of the target), if the failure policy didn't abort the session.
+	pub fn hook_error(&self) -> Option<&Error> {
+		self.hook_error.as_ref()
+	}
+
+	/// Run the "setup" command, if configured.
+	pub fn setup(&mut self) -> Result<(), Error> {
+		self.hook_error = None;
+		self.hook("setup", self.hooks.setup)
+	}
+
+	/// Run the "cleanup" command, if configured.
+	pub fn cleanup(&mut self) -> Result<(), Error> {
+		self.hook_error = None;
+		self.hook("cleanup", self.hooks.cleanup)
+	}
+
+	/// Run cmd (if configured) as the named hook, recording any failure that
+	/// doesn't abort the session.
+	fn hook(&mut self, name: &'static str, cmd: Option<&str>) -> Result<(), Error> {
+		let cmd = match cmd {
+			Some(cmd) => cmd,
+			None => return Ok(()),
+		};
+
+		let status = runner::shell(cmd, &self.options)?;
+		if status.success() {
+			return Ok(());
+		}
+
+		let err = Error::HookFailed(name, status);
+		if self.on_failure == Policy::Abort {
+			return Err(err);
+		}
+		self.hook_error = Some(err);
+		Ok(())
+	}
+
+	/// Execute the target once, surrounded by the "prepare" and "conclude"
+	/// commands, returning the runtime and exit status.
+	fn exec(&mut self, name: &str) -> Result<(Duration, Status), Error> {
+		self.hook_error = None;
+		self.hook("prepare", self.hooks.prepare)?;
+
+		let (runtime, status) = self.exec_target(name)?;
+
+		self.hook("conclude", self.hooks.conclude)?;
+		Ok((runtime, status))
+	}
+
+	/// Execute the target once (or measure an interval of the attached
+	/// process), returning the runtime and exit status.
+	fn exec_target(&mut self, name: &str) -> Result<(Duration, Status), Error> {
+		if let Some(ref mut attach) = self.attach {
+			let start = Instant::now();
+			let status = attach.run(&mut self.counters)?;
+			return Ok((start.elapsed(), status));
+		}
+
+		let mut options = self.options.clone();
+		if let Some(ref dir) = self.output_dir {
+			fs::create_dir_all(dir).map_err(|e| {
+				Error::ExecError(format!("failed to create {}: {}", dir.display(), e))
+			})?;
+			options.output = Output::File(dir.join(format!("{}.log", name)));
+		}
+
+		let mut runner = Runner::new(self.target).args(&self.args).options(options);
+
+		let start = Instant::now();
+		let status = runner.run(&mut self.counters)?;
+		let runtime = start.elapsed();
+
+		self.output.clear();
+		if !status.success() {
+			self.output.extend_from_slice(runner.output());
+		}
+
+		Ok((runtime, status))
+	}
+
+	/// Returns the number of runs included in the results.
+	fn included(&self) -> usize {
+		self.runs.iter().filter(|r| !r.excluded).count()
+	}
+
+	/// Execute the target once with the counters attached, discarding the
+	/// counter values so the run has no effect on the results (i.e. to warm
+	/// caches before measuring). Returns the runtime of the target.
+	pub fn warmup(&mut self) -> Result<Duration, Error> {
+		self.warmups += 1;
+		let name = format!("warmup-{}", self.warmups);
+		let (runtime, status) = self.exec(&name)?;
+
+		// The value read at the end of the run is the one after the last
+		// included run
+		let value = self.included();
+		for c in &mut self.counters {
+			c.exclude(value);
+		}
+
+		if !status.success() && self.policy(status) == Policy::Abort {
+			return Err(status.into());
+		}
+
+		Ok(runtime)
+	}
+
+	/// Measure the overhead of executing target with args (i.e. an empty shell
+	/// command, or a target that exits immediately) by running it runs times
+	/// with the counters attached, and subtract the median value of each event
+	/// from the value observed in every subsequent run.
+	///
+	/// The values observed running target are discarded.
+	pub fn calibrate(&mut self, target: &str, args: &[&str], runs: usize) -> Result<(), Error> {
+		let first = self.included();
+		let mut runner = Runner::new(target).args(args).options(self.options.clone());
+
+		let mut result = Ok(());
+		for _ in 0..runs {
+			match runner.run(&mut self.counters) {
+				Ok(status) if status.success() => (),
+				Ok(status) => result = Err(status.into()),
+				Err(err) => result = Err(err),
+			}
+			if result.is_err() {
+				break;
+			}
+		}
+
+		if result.is_ok() && runs > 0 {
+			self.overhead = self
+				.counters
+				.iter()
+				.flat_map(|c| c.samples())
+				.map(|s| (s.name.to_string(), stats::median(&s.values[first..]) as u64))
+				.collect();
+		}
+
+		// Drop the calibration values, leaving those of the included runs
+		let observed = self
+			.counters
+			.iter()
+			.flat_map(|c| c.samples())
+			.map(|s| s.values.len().saturating_sub(first))
+			.max()
+			.unwrap_or(0);
+		for _ in 0..observed {
+			for c in &mut self.counters {
+				c.exclude(first);
+			}
+		}
+
+		result
+	}
+
+	/// Returns the value subtracted from each event every run, as measured by
+	/// [`calibrate`](#method.calibrate).
+	pub fn overhead(&self) -> &[(String, u64)] {
+		&self.overhead
+	}
+
+	/// Returns the name of each event along with the mean value observed
+	/// before the overhead was subtracted, or nothing if not calibrated.
+	pub fn raw(&self) -> Vec<(&str, f64)> {
+		if self.raw.is_empty() {
+			return vec![];
+		}
+
+		self.counters
+			.iter()
+			.flat_map(|c| c.samples())
+			.enumerate()
+			.map(|(i, s)| {
+				let values: Vec<u64> = self.raw.iter().map(|r| r[i]).collect();
+				(s.name, stats::mean(&values))
+			})
+			.collect()
+	}
+
+	/// Compare the observed counter values against baseline when displayed.
+	pub fn compare(&mut self, baseline: &Baseline) {
+		for c in &mut self.counters {
+			c.compare(baseline);
+		}
+	}
+
+	/// Returns the largest relative standard deviation of the values observed
+	/// for the events named event (matching either the alias or the event
+	/// specifier), or of the first event if event is None.
+	///
+	/// The first event is the primary one (i.e. `instructions`, or the first
+	/// user specified event) - rarely occurring events such as
+	/// `cpu-migrations` have small counts that vary wildly between runs.
+	///
+	/// Returns None if no event matches.
+	pub fn rsd(&self, event: Option<&str>) -> Option<f64> {
+		let mut samples = self.counters.iter().flat_map(|c| c.samples());
+		let event = match event {
+			Some(event) => event,
+			None => return samples.next().map(|s| stats::rsd(s.values)),
+		};
+
+		samples
+			.filter(|s| s.name == event || s.spec == event)
+			.map(|s| stats::rsd(s.values))
+			.fold(None, |max, rsd| Some(max.map_or(rsd, |m: f64| m.max(rsd))))
+	}
+
+	/// Returns the name of each event along with the values observed that are
+	/// outliers (see [`stats::outliers`]), indexed by included run.
+	///
+	/// [`stats::outliers`]: ../stats/fn.outliers.html
+	pub fn outliers(&self) -> Vec<(&str, Vec<usize>)> {
+		self.counters
+			.iter()
+			.flat_map(|c| c.samples())
+			.map(|s| (s.name, stats::outliers(s.values)))
+			.collect()
+	}
+
+	/// Exclude every run in which the value of any event is an outlier,
+	/// dropping the values of all events observed in the run to keep them
+	/// consistent. Returns the number of runs excluded.
+	pub fn reject_outliers(&mut self) -> usize {
+		let mut values: Vec<usize> = self.outliers().into_iter().flat_map(|(_, r)| r).collect();
+		values.sort_unstable();
+		values.dedup();
+
+		let included: Vec<usize> = (0..self.runs.len()).filter(|&i| !self.runs[i].excluded).collect();
+
+		// Remove from the back so the remaining indexes are unaffected
+		for &value in values.iter().rev() {
+			for c in &mut self.counters {
+				c.exclude(value);
+			}
+			if value < self.raw.len() {
+				self.raw.remove(value);
+			}
+			self.runs[included[value]].excluded = true;
+		}
+
+		values.len()
+	}
+
+	pub fn report(&self) -> Report<'_> {
+		Report {
+			target: self.target,
+			args: &self.args,
+			runs: &self.runs,
+			samples: self.counters.iter().flat_map(|c| c.samples()).collect(),
+		}
+	}
+}
+
+/// Split a command line into the target and its arguments, following the
+/// quoting rules of sh(1) for single quotes, double quotes and backslash
+/// escapes (but not expansions of any kind).
+pub fn split(cmd: &str) -> Result<Vec<String>, String> {
+	let mut words = vec![];
+	let mut word = String::new();
+	let mut in_word = false;
+
+	let mut chars = cmd.chars();
+	while let Some(c) = chars.next() {
+		match c {
+			'\'' => {
+				in_word = true;
+				loop {
+					match chars.next() {
+						Some('\'') => break,
+						Some(c) => word.push(c),
+						None => return Err(format!("unterminated quote in '{}'", cmd)),
+					}
+				}
+			}
+			'"' => {
+				in_word = true;
+				loop {
+					match chars.next() {
+						Some('"') => break,
+						Some('\\') => match chars.next() {
+							Some(c) if c == '"' || c == '\\' => word.push(c),
+							Some(c) => {
+								word.push('\\');
+								word.push(c);
+							}
+							None => return Err(format!("unterminated quote in '{}'", cmd)),
+						},
+						Some(c) => word.push(c),
+						None => return Err(format!("unterminated quote in '{}'", cmd)),
+					}
+				}
+			}
+			'\\' => {
+				in_word = true;
+				match chars.next() {
+					Some(c) => word.push(c),
+					None => return Err(format!("trailing backslash in '{}'", cmd)),
+				}
+			}
+			c if c.is_whitespace() => {
+				if in_word {
+					words.push(word);
+					word = String::new();
+					in_word = false;
+				}
+			}
+			c => {
+				in_word = true;
+				word.push(c);
+			}
+		}
+	}
+
+	if in_word {
+		words.push(word);
+	}
+
+	if words.is_empty() {
+		return Err(String::from("empty command"));
+	}
+
+	Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+	mod mock_event;
+
+	use self::mock_event::MockEvent;
+	use super::*;
+	use event::RSDPrinter;
+
+	/// Returns a session running true(1), with a counter observing values in
+	/// order.
+	fn session(values: &[u64]) -> Session<'static> {
+		let counter = RSDPrinter::new(MockEvent::new(values));
+		Session::new("true", vec![], vec![Box::new(counter)])
+	}
+
+	/// Returns the values of the counter included in the results.
+	fn values(s: &Session) -> Vec<u64> {
+		s.report().samples[0].values.to_vec()
+	}
+
+	#[test]
+	fn test_warmup() {
+		let mut s = session(&[1, 2, 3, 4]);
+
+		assert!(s.warmup().is_ok());
+		assert!(s.warmup().is_ok());
+		assert!(s.run().is_ok());
+		assert!(s.run().is_ok());
+
+		// The warmups consumed the first values, which are discarded
+		assert_eq!(values(&s), vec![3, 4]);
+		assert_eq!(s.report().runs.len(), 2);
+	}
+
+	#[test]
+	fn test_calibrate() {
+		let mut s = session(&[10, 30, 20, 100, 5]);
+
+		assert!(s.calibrate("true", &[], 3).is_ok());
+		assert_eq!(s.overhead(), &[(String::from("mock"), 20)]);
+
+		// The calibration values are discarded
+		assert!(values(&s).is_empty());
+
+		assert!(s.run().is_ok());
+		assert!(s.run().is_ok());
+
+		// The overhead is subtracted, saturating at zero
+		assert_eq!(values(&s), vec![80, 0]);
+		assert_eq!(s.raw(), vec![("mock", 52.5)]);
+	}
+
+	#[test]
+	fn test_calibrate_shell() {
+		// As run for --shell-overhead, measuring the shell in place of the
+		// null target
+		let mut s = session(&[1, 2, 3, 6, 8, 50]);
+
+		assert!(s.run().is_ok());
+		assert!(s.calibrate("sh", &["-c", ""], 2).is_ok());
+		assert_eq!(s.overhead(), &[(String::from("mock"), 2)]);
+
+		// Measuring again replaces the overhead rather than adding to it
+		assert!(s.calibrate("sh", &["-c", ""], 2).is_ok());
+		assert_eq!(s.overhead(), &[(String::from("mock"), 7)]);
+
+		// Runs before calibrating are left as observed
+		assert!(s.run().is_ok());
+		assert_eq!(values(&s), vec![1, 43]);
+		assert_eq!(s.raw(), vec![("mock", 50.0)]);
+	}
+
+	#[test]
+	fn test_calibrate_err() {
+		let mut s = session(&[10, 20, 30]);
+
+		assert!(s.calibrate("false", &[], 2).is_err());
+
+		// Nothing is subtracted if the overhead couldn't be measured
+		assert!(s.overhead().is_empty());
+		assert!(s.run().is_ok());
+		assert_eq!(values(&s), vec![20]);
+		assert!(s.raw().is_empty());
+	}
+
+	#[test]
+	fn test_rsd() {
+		let counters: Vec<Box<dyn DisplayCounter>> = vec![
+			Box::new(RSDPrinter::new(MockEvent::new(&[100, 100]))),
+			Box::new(RSDPrinter::new(MockEvent::new(&[1, 3]))),
+		];
+		let mut s = Session::new("true", vec![], counters);
+		assert!(s.run().is_ok());
+		assert!(s.run().is_ok());
+
+		// Only the first event is considered by default
+		assert_eq!(s.rsd(None), Some(0.0));
+		assert_eq!(s.rsd(Some("mock")), Some(stats::rsd(&[1, 3])));
+		assert_eq!(s.rsd(Some("other")), None);
+	}
+
+	#[test]
+	fn test_split() {
+		assert_eq!(split("./a.test"), Ok(vec![String::from("./a.test")]));
+		assert_eq!(
+			split("  ./a.test  -run=^$ \t-test.bench=A "),
+			Ok(vec![
+				String::from("./a.test"),
+				String::from("-run=^$"),
+				String::from("-test.bench=A"),
+			])
+		);
+		assert_eq!(
+			split(r#"a 'b c' "d \"e\" \n" f\ g ''"#),
+			Ok(vec![
+				String::from("a"),
+				String::from("b c"),
+				String::from("d \"e\" \\n"),
+				String::from("f g"),
+				String::from(""),
+			])
+		);
+	}
+
+	#[test]
+	fn test_split_err() {
+		assert!(split("").is_err());
+		assert!(split("   ").is_err());
+		assert!(split("a 'b").is_err());
+		assert!(split("a \"b").is_err());
+		assert!(split("a \\").is_err());
+	}
+}
@@ -0,0 +1,54 @@
+use error::Error;
+use event::Printable;
+use runner::Counter;
+
+use std::fmt;
+
+/// `MockEvent` returns the given values in order, one per run.
+pub struct MockEvent {
+	values: Vec<u64>,
+	value: u64,
+}
+
+impl Counter for MockEvent {
+	fn attach(&mut self, _pid: u32) -> Result<(), Error> {
+		Ok(())
+	}
+	fn start(&mut self) -> Result<(), Error> {
+		Ok(())
+	}
+	fn stop(&mut self) -> Result<(), Error> {
+		Ok(())
+	}
+	fn set(&mut self, _value: u64) -> Result<u64, Error> {
+		self.value = self.values.remove(0);
+		Ok(self.value)
+	}
+}
+
+impl fmt::Display for MockEvent {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "mock")
+	}
+}
+
+impl Printable for MockEvent {
+	fn name(&self) -> &str {
+		"mock"
+	}
+	fn spec(&self) -> &str {
+		"mock"
+	}
+	fn value(&self) -> u64 {
+		self.value
+	}
+}
+
+impl MockEvent {
+	pub fn new(values: &[u64]) -> Self {
+		MockEvent {
+			values: values.to_vec(),
+			value: 0,
+		}
+	}
+}
@@ -0,0 +1,794 @@
+//! Descriptive statistics and significance tests over observed counter
+//! values.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// `SIGNIFICANCE` is the p-value below which a difference between two samples
+/// is reported as statistically significant.
+pub const SIGNIFICANCE: f64 = 0.05;
+
+/// `CONFIDENCE` is the confidence level of reported confidence intervals.
+pub const CONFIDENCE: f64 = 0.95;
+
+/// mean returns the arithmetic mean of values, or 0 if values is empty.
+pub fn mean(values: &[u64]) -> f64 {
+	if values.is_empty() {
+		return 0.0;
+	}
+
+	values.iter().sum::<u64>() as f64 / values.len() as f64
+}
+
+/// variance returns the sample variance of values.
+pub fn variance(values: &[u64]) -> f64 {
+	if values.len() < 2 {
+		// Don't panic on division of (len - 1) below
+		return 0.0;
+	}
+
+	let mean = mean(values);
+	let total = values.iter().fold(0.0, |acc, v| {
+		let x = *v as f64 - mean;
+		acc + x * x
+	});
+
+	total / (values.len() - 1) as f64
+}
+
+/// stddev returns the sample standard deviation of values.
+pub fn stddev(values: &[u64]) -> f64 {
+	variance(values).sqrt()
+}
+
+/// rsd returns the [relative standard deviation] of values as a percentage.
+///
+/// [relative standard deviation]: https://en.wikipedia.org/wiki/Coefficient_of_variation
+pub fn rsd(values: &[u64]) -> f64 {
+	let mean = mean(values);
+	if values.len() < 2 || mean == 0.0 {
+		// Don't panic on division of 0
+		return 0.0;
+	}
+
+	(stddev(values) * 100.0) / mean
+}
+
+/// percentile returns the p-th percentile (0 <= p <= 100) of values, linearly
+/// interpolating between the closest ranks.
+pub fn percentile(values: &[u64], p: f64) -> f64 {
+	let mut sorted: Vec<_> = values.iter().map(|&v| v as f64).collect();
+	sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+	interpolate(&sorted, p)
+}
+
+/// interpolate returns the p-th percentile of the ascending sorted values.
+fn interpolate(sorted: &[f64], p: f64) -> f64 {
+	if sorted.is_empty() {
+		return 0.0;
+	}
+
+	let rank = (p / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+	let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+
+	sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64)
+}
+
+/// median returns the median of values.
+pub fn median(values: &[u64]) -> f64 {
+	percentile(values, 50.0)
+}
+
+/// min returns the smallest of values, or 0 if values is empty.
+pub fn min(values: &[u64]) -> f64 {
+	values.iter().min().cloned().unwrap_or(0) as f64
+}
+
+/// max returns the largest of values, or 0 if values is empty.
+pub fn max(values: &[u64]) -> f64 {
+	values.iter().max().cloned().unwrap_or(0) as f64
+}
+
+/// mad returns the [median absolute deviation] of values (unscaled).
+///
+/// [median absolute deviation]: https://en.wikipedia.org/wiki/Median_absolute_deviation
+pub fn mad(values: &[u64]) -> f64 {
+	let median = median(values);
+
+	let mut deviations: Vec<_> = values.iter().map(|&v| (v as f64 - median).abs()).collect();
+	deviations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+	interpolate(&deviations, 50.0)
+}
+
+/// `OUTLIER_FENCE` is the multiple of the interquartile range beyond the
+/// quartiles at which a value is classified as an outlier.
+pub const OUTLIER_FENCE: f64 = 1.5;
+
+/// outliers returns the indexes of the values lying outside the [Tukey fences]
+/// of values, in ascending order.
+///
+/// Counters are integers, so the interquartile range is never considered to be
+/// smaller than a single count (or 1% of the median) - otherwise every run of
+/// a near-constant counter (i.e. page faults) that differs by one would be
+/// flagged.
+///
+/// [Tukey fences]: https://en.wikipedia.org/wiki/Outlier#Tukey's_fences
+pub fn outliers(values: &[u64]) -> Vec<usize> {
+	if values.len() < 4 {
+		// Quartiles of so few values are meaningless
+		return vec![];
+	}
+
+	let mut sorted: Vec<_> = values.iter().map(|&v| v as f64).collect();
+	sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+	let (q1, q3) = (interpolate(&sorted, 25.0), interpolate(&sorted, 75.0));
+	let iqr = (q3 - q1).max(interpolate(&sorted, 50.0) * 0.01).max(1.0);
+	let (lo, hi) = (q1 - OUTLIER_FENCE * iqr, q3 + OUTLIER_FENCE * iqr);
+
+	values
+		.iter()
+		.enumerate()
+		.filter(|&(_, &v)| (v as f64) < lo || (v as f64) > hi)
+		.map(|(i, _)| i)
+		.collect()
+}
+
+/// `Center` selects the measure of central tendency used to summarise observed
+/// values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Center {
+	Mean,
+	Median,
+	Min,
+	Max,
+	P5,
+	P95,
+}
+
+impl Center {
+	pub fn of(self, values: &[u64]) -> f64 {
+		match self {
+			Center::Mean => mean(values),
+			Center::Median => median(values),
+			Center::Min => min(values),
+			Center::Max => max(values),
+			Center::P5 => percentile(values, 5.0),
+			Center::P95 => percentile(values, 95.0),
+		}
+	}
+}
+
+impl FromStr for Center {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"mean" => Ok(Center::Mean),
+			"median" => Ok(Center::Median),
+			"min" => Ok(Center::Min),
+			"max" => Ok(Center::Max),
+			"p5" => Ok(Center::P5),
+			"p95" => Ok(Center::P95),
+			_ => Err(format!("unknown central tendency '{}'", s)),
+		}
+	}
+}
+
+/// `Spread` selects the measure of dispersion used to summarise observed
+/// values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Spread {
+	/// Relative standard deviation, as a percentage of the mean.
+	Rsd,
+	/// Median absolute deviation, as a percentage of the median.
+	Rmad,
+	Stddev,
+	Mad,
+}
+
+impl Spread {
+	pub fn of(self, values: &[u64]) -> f64 {
+		match self {
+			Spread::Rsd => rsd(values),
+			Spread::Rmad => {
+				let median = median(values);
+				if median == 0.0 {
+					return 0.0;
+				}
+				mad(values) * 100.0 / median
+			}
+			Spread::Stddev => stddev(values),
+			Spread::Mad => mad(values),
+		}
+	}
+
+	/// Returns true if the spread is a percentage rather than an absolute
+	/// value.
+	pub fn is_relative(self) -> bool {
+		match self {
+			Spread::Rsd | Spread::Rmad => true,
+			Spread::Stddev | Spread::Mad => false,
+		}
+	}
+}
+
+impl FromStr for Spread {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"rsd" => Ok(Spread::Rsd),
+			"rmad" => Ok(Spread::Rmad),
+			"stddev" => Ok(Spread::Stddev),
+			"mad" => Ok(Spread::Mad),
+			_ => Err(format!("unknown dispersion '{}'", s)),
+		}
+	}
+}
+
+/// `TTest` is the outcome of a two-sample t-test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TTest {
+	/// The t statistic.
+	pub t: f64,
+	/// Degrees of freedom.
+	pub df: f64,
+	/// Two-sided p-value.
+	pub p: f64,
+}
+
+/// welch performs [Welch's t-test] of the null hypothesis that a and b have
+/// equal means, without assuming equal variances.
+///
+/// Returns `None` when either sample has fewer than two values.
+///
+/// [Welch's t-test]: https://en.wikipedia.org/wiki/Welch%27s_t-test
+pub fn welch(a: &[u64], b: &[u64]) -> Option<TTest> {
+	if a.len() < 2 || b.len() < 2 {
+		return None;
+	}
+
+	let (na, nb) = (a.len() as f64, b.len() as f64);
+	let (va, vb) = (variance(a) / na, variance(b) / nb);
+	let diff = mean(a) - mean(b);
+
+	if va + vb == 0.0 {
+		// Both samples are constant - they either differ or they don't.
+		let p = if diff == 0.0 { 1.0 } else { 0.0 };
+		let t = if diff == 0.0 { 0.0 } else { diff.signum() * f64::INFINITY };
+		return Some(TTest {
+			t,
+			df: na + nb - 2.0,
+			p,
+		});
+	}
+
+	let t = diff / (va + vb).sqrt();
+	let df = (va + vb).powi(2) / (va.powi(2) / (na - 1.0) + vb.powi(2) / (nb - 1.0));
+
+	Some(TTest {
+		t,
+		df,
+		p: t_sf2(t, df),
+	})
+}
+
+/// `UTest` is the outcome of a Mann-Whitney U test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UTest {
+	/// The U statistic of the first sample.
+	pub u: f64,
+	/// Two-sided p-value.
+	pub p: f64,
+}
+
+/// mann_whitney performs a [Mann-Whitney U test] of the null hypothesis that a
+/// value drawn from a is equally likely to be greater or less than a value
+/// drawn from b.
+///
+/// Unlike the t-test it makes no assumption about the distribution of the
+/// values, so is robust against the odd outlier run. The p-value uses the
+/// normal approximation with tie and continuity corrections.
+///
+/// Returns `None` when either sample has fewer than two values.
+///
+/// [Mann-Whitney U test]: https://en.wikipedia.org/wiki/Mann%E2%80%93Whitney_U_test
+pub fn mann_whitney(a: &[u64], b: &[u64]) -> Option<UTest> {
+	if a.len() < 2 || b.len() < 2 {
+		return None;
+	}
+
+	// Rank the combined samples, assigning tied values the average of the
+	// ranks they span.
+	let mut all: Vec<(u64, bool)> = a
+		.iter()
+		.map(|&v| (v, true))
+		.chain(b.iter().map(|&v| (v, false)))
+		.collect();
+	all.sort_by_key(|&(v, _)| v);
+
+	let mut rank_sum = 0.0;
+	let mut ties = 0.0;
+	let mut i = 0;
+	while i < all.len() {
+		let mut j = i;
+		while j < all.len() && all[j].0 == all[i].0 {
+			j += 1;
+		}
+
+		// Ranks are 1-based, so the span i..j covers ranks i+1 ..= j
+		let rank = (i + 1 + j) as f64 / 2.0;
+		rank_sum += rank * all[i..j].iter().filter(|&&(_, in_a)| in_a).count() as f64;
+
+		let t = (j - i) as f64;
+		ties += t * t * t - t;
+		i = j;
+	}
+
+	let (na, nb) = (a.len() as f64, b.len() as f64);
+	let n = na + nb;
+
+	let u = rank_sum - na * (na + 1.0) / 2.0;
+	let mu = na * nb / 2.0;
+	let sigma = (na * nb / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)))).sqrt();
+
+	if sigma == 0.0 {
+		// Every value is identical
+		return Some(UTest { u, p: 1.0 });
+	}
+
+	let z = ((u - mu).abs() - 0.5).max(0.0) / sigma;
+	Some(UTest {
+		u,
+		p: erfc(z / std::f64::consts::SQRT_2),
+	})
+}
+
+/// `Comparison` summarises the difference between a reference sample and
+/// another sample of the same event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+	/// The mean of the reference sample.
+	pub base: f64,
+	/// The difference of the means (other - reference).
+	pub diff: f64,
+	/// The [`CONFIDENCE`] interval of diff.
+	///
+	/// [`CONFIDENCE`]: constant.CONFIDENCE.html
+	pub ci: (f64, f64),
+	pub welch: TTest,
+	pub mann_whitney: UTest,
+}
+
+impl Comparison {
+	/// Returns true if the difference is statistically significant according
+	/// to Welch's t-test.
+	pub fn significant(&self) -> bool {
+		self.welch.p < SIGNIFICANCE
+	}
+}
+
+impl fmt::Display for Comparison {
+	/// Prints the p-values of the t-test and U test, and the confidence interval
+	/// of the difference relative to the reference mean:
+	///
+	/// ```text
+	/// p=0.000 u=0.008 [-20.5%, -19.9%] *
+	/// ```
+	///
+	/// A trailing `*` marks a significant difference.
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let ci = if self.base == 0.0 {
+			String::from("[-]")
+		} else {
+			format!(
+				"[{:+.1}%, {:+.1}%]",
+				self.ci.0 * 100.0 / self.base,
+				self.ci.1 * 100.0 / self.base
+			)
+		};
+
+		write!(
+			f,
+			"p={:.3} u={:.3} {:<17}{}",
+			self.welch.p,
+			self.mann_whitney.p,
+			ci,
+			if self.significant() { " *" } else { "  " }
+		)
+	}
+}
+
+/// compare tests the difference between the reference sample base and other.
+///
+/// Returns `None` when either sample has fewer than two values.
+pub fn compare(base: &[u64], other: &[u64]) -> Option<Comparison> {
+	let welch = welch(base, other)?;
+	let mann_whitney = mann_whitney(base, other)?;
+
+	let diff = mean(other) - mean(base);
+	let se = (variance(base) / base.len() as f64 + variance(other) / other.len() as f64).sqrt();
+	let half = if se == 0.0 {
+		0.0
+	} else {
+		t_quantile2(1.0 - CONFIDENCE, welch.df) * se
+	};
+
+	Some(Comparison {
+		base: mean(base),
+		diff,
+		ci: (diff - half, diff + half),
+		welch,
+		mann_whitney,
+	})
+}
+
+/// t_quantile2 returns the critical value t such that P(|T| > t) = alpha for
+/// the Student's t-distribution with df degrees of freedom.
+fn t_quantile2(alpha: f64, df: f64) -> f64 {
+	let (mut lo, mut hi) = (0.0, 1.0);
+	while t_sf2(hi, df) > alpha {
+		lo = hi;
+		hi *= 2.0;
+	}
+
+	// The tail probability is monotonic, so bisect
+	for _ in 0..100 {
+		let mid = (lo + hi) / 2.0;
+		if t_sf2(mid, df) > alpha {
+			lo = mid;
+		} else {
+			hi = mid;
+		}
+	}
+
+	(lo + hi) / 2.0
+}
+
+/// erfc returns the complementary error function (Chebyshev approximation,
+/// fractional error below 1.2e-7).
+fn erfc(x: f64) -> f64 {
+	let z = x.abs();
+	let t = 1.0 / (1.0 + 0.5 * z);
+	let r = t * (-z * z - 1.265_512_23
+		+ t * (1.000_023_68
+			+ t * (0.374_091_96
+				+ t * (0.096_784_18
+					+ t * (-0.186_288_06
+						+ t * (0.278_868_07
+							+ t * (-1.135_203_98
+								+ t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77)))))))))
+		.exp();
+
+	if x >= 0.0 {
+		r
+	} else {
+		2.0 - r
+	}
+}
+
+/// t_sf2 returns the two-sided tail probability P(|T| > |t|) of the Student's
+/// t-distribution with df degrees of freedom.
+fn t_sf2(t: f64, df: f64) -> f64 {
+	beta_inc(df / 2.0, 0.5, df / (df + t * t))
+}
+
+/// ln_gamma returns the natural logarithm of the gamma function (Lanczos
+/// approximation, accurate to ~15 significant figures for x > 0).
+fn ln_gamma(x: f64) -> f64 {
+	const G: [f64; 9] = [
+		0.999_999_999_999_809_9,
+		676.520_368_121_885_1,
+		-1_259.139_216_722_402_8,
+		771.323_428_777_653_1,
+		-176.615_029_162_140_6,
+		12.507_343_278_686_905,
+		-0.138_571_095_265_720_12,
+		9.984_369_578_019_572e-6,
+		1.505_632_735_149_311_6e-7,
+	];
+
+	if x < 0.5 {
+		// Reflection formula
+		let pi = std::f64::consts::PI;
+		return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+	}
+
+	let x = x - 1.0;
+	let mut sum = G[0];
+	for (i, g) in G.iter().enumerate().skip(1) {
+		sum += g / (x + i as f64);
+	}
+
+	let t = x + 7.5;
+	0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// beta_inc returns the regularised incomplete beta function I_x(a, b).
+fn beta_inc(a: f64, b: f64, x: f64) -> f64 {
+	if x <= 0.0 {
+		return 0.0;
+	}
+	if x >= 1.0 {
+		return 1.0;
+	}
+
+	let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+
+	// The continued fraction converges quickly for x < (a + 1) / (a + b + 2),
+	// otherwise use the symmetry relation.
+	if x < (a + 1.0) / (a + b + 2.0) {
+		front * beta_cf(a, b, x) / a
+	} else {
+		1.0 - front * beta_cf(b, a, 1.0 - x) / b
+	}
+}
+
+/// beta_cf evaluates the continued fraction for the incomplete beta function
+/// using the modified Lentz's method.
+fn beta_cf(a: f64, b: f64, x: f64) -> f64 {
+	const EPSILON: f64 = 1e-15;
+	const TINY: f64 = 1e-300;
+
+	let mut c = 1.0;
+	let mut d = 1.0 - (a + b) * x / (a + 1.0);
+	if d.abs() < TINY {
+		d = TINY;
+	}
+	d = 1.0 / d;
+	let mut h = d;
+
+	for m in 1..500 {
+		let m = f64::from(m);
+		let m2 = 2.0 * m;
+
+		// Even step
+		let aa = m * (b - m) * x / ((a + m2 - 1.0) * (a + m2));
+		d = 1.0 + aa * d;
+		if d.abs() < TINY {
+			d = TINY;
+		}
+		c = 1.0 + aa / c;
+		if c.abs() < TINY {
+			c = TINY;
+		}
+		d = 1.0 / d;
+		h *= d * c;
+
+		// Odd step
+		let aa = -(a + m) * (a + b + m) * x / ((a + m2) * (a + m2 + 1.0));
+		d = 1.0 + aa * d;
+		if d.abs() < TINY {
+			d = TINY;
+		}
+		c = 1.0 + aa / c;
+		if c.abs() < TINY {
+			c = TINY;
+		}
+		d = 1.0 / d;
+		let delta = d * c;
+		h *= delta;
+
+		if (delta - 1.0).abs() < EPSILON {
+			break;
+		}
+	}
+
+	h
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_stats() {
+		let values = [0, 10, 20, 30, 40];
+
+		assert_eq!(mean(&values), 20.0);
+		assert_eq!(variance(&values), 250.0);
+		assert_eq!(stddev(&values) as f32, 15.811_388);
+		assert_eq!(rsd(&values) as f32, 79.056_94);
+	}
+
+	#[test]
+	fn test_fractional_mean() {
+		let values = [1, 2];
+
+		assert_eq!(mean(&values), 1.5);
+		assert_eq!(variance(&values), 0.5);
+	}
+
+	fn assert_near(got: f64, want: f64) {
+		assert!((got - want).abs() < 1e-6, "got {}, want {}", got, want);
+	}
+
+	#[test]
+	fn test_ln_gamma() {
+		assert_near(ln_gamma(1.0), 0.0);
+		assert_near(ln_gamma(0.5), std::f64::consts::PI.sqrt().ln());
+		assert_near(ln_gamma(10.0), 362_880f64.ln());
+	}
+
+	#[test]
+	fn test_t_sf2() {
+		// Reference values from the Student's t-distribution tables
+		assert_near(t_sf2(0.0, 10.0), 1.0);
+		assert_near(t_sf2(2.228_138_85, 10.0), 0.05);
+		assert_near(t_sf2(-2.228_138_85, 10.0), 0.05);
+		assert_near(t_sf2(12.706_204_7, 1.0), 0.05);
+		assert_near(t_sf2(3.169_272_67, 10.0), 0.01);
+	}
+
+	#[test]
+	fn test_welch() {
+		let a = [19, 20, 21, 20, 20];
+		let b = [29, 30, 31, 30, 30];
+		let r = welch(&a, &b).unwrap();
+		assert_near(r.t, -22.360_679_77);
+		assert_near(r.df, 8.0);
+		assert!(r.p < 1e-6);
+
+		// Indistinguishable samples
+		let r = welch(&a, &[20, 21, 19, 20, 20]).unwrap();
+		assert_near(r.t, 0.0);
+		assert_near(r.p, 1.0);
+
+		// Unequal variances
+		let r = welch(&[10, 12, 14, 16, 18], &[11, 11, 12, 12, 13]).unwrap();
+		assert_near(r.t, 1.503_889_039_454_259);
+		assert_near(r.df, 4.557_269_380_037_815);
+		assert_near(r.p, 0.198_468_517);
+	}
+
+	#[test]
+	fn test_welch_constant() {
+		let r = welch(&[5, 5], &[5, 5]).unwrap();
+		assert_eq!(r.p, 1.0);
+
+		let r = welch(&[5, 5], &[6, 6]).unwrap();
+		assert_eq!(r.p, 0.0);
+
+		assert_eq!(welch(&[5], &[6, 6]), None);
+	}
+
+	#[test]
+	fn test_erfc() {
+		assert_near(erfc(0.0), 1.0);
+		assert!((erfc(1.0) - 0.157_299_207).abs() < 1e-6);
+		assert!((erfc(-1.0) - 1.842_700_793).abs() < 1e-6);
+		assert!((erfc(1.959_963_985 / std::f64::consts::SQRT_2) - 0.05).abs() < 1e-6);
+	}
+
+	#[test]
+	fn test_t_quantile2() {
+		assert!((t_quantile2(0.05, 10.0) - 2.228_138_85).abs() < 1e-6);
+		assert!((t_quantile2(0.05, 1.0) - 12.706_204_7).abs() < 1e-5);
+		assert!((t_quantile2(0.01, 10.0) - 3.169_272_67).abs() < 1e-6);
+	}
+
+	#[test]
+	fn test_mann_whitney() {
+		// Completely separated samples
+		let r = mann_whitney(&[1, 2, 3, 4, 5], &[6, 7, 8, 9, 10]).unwrap();
+		assert_eq!(r.u, 0.0);
+		// z = (12.5 - 0.5) / sqrt(25 * 11 / 12)
+		assert!((r.p - 0.012_185).abs() < 1e-4, "p = {}", r.p);
+
+		// Interleaved samples
+		let r = mann_whitney(&[1, 3, 5, 7, 9], &[2, 4, 6, 8, 10]).unwrap();
+		assert_eq!(r.u, 10.0);
+		assert!(r.p > 0.5);
+
+		// Ties are ranked with the average rank
+		let r = mann_whitney(&[1, 2, 2], &[2, 3, 3]).unwrap();
+		assert_eq!(r.u, 1.0);
+
+		// All identical
+		let r = mann_whitney(&[4, 4], &[4, 4]).unwrap();
+		assert_eq!(r.p, 1.0);
+
+		assert_eq!(mann_whitney(&[1], &[1, 2]), None);
+	}
+
+	#[test]
+	fn test_compare() {
+		let c = compare(&[19, 20, 21, 20, 20], &[29, 30, 31, 30, 30]).unwrap();
+		assert_eq!(c.base, 20.0);
+		assert_eq!(c.diff, 10.0);
+
+		// se = sqrt(0.2), t(0.975, df=8) = 2.306004
+		let half = 2.306_004_135 * 0.2_f64.sqrt();
+		assert!((c.ci.0 - (10.0 - half)).abs() < 1e-6);
+		assert!((c.ci.1 - (10.0 + half)).abs() < 1e-6);
+		assert!(c.significant());
+		assert_eq!(format!("{}", c), "p=0.000 u=0.010 [+44.8%, +55.2%]  *");
+
+		let c = compare(&[5, 5], &[5, 5]).unwrap();
+		assert_eq!(c.ci, (0.0, 0.0));
+		assert!(!c.significant());
+
+		let c = compare(&[0, 0], &[5, 5]).unwrap();
+		assert_eq!(format!("{}", c), "p=0.000 u=0.194 [-]               *");
+
+		assert_eq!(compare(&[1], &[1, 2]), None);
+	}
+
+	#[test]
+	fn test_percentile() {
+		let values = [40, 10, 30, 20, 0];
+
+		assert_eq!(median(&values), 20.0);
+		assert_eq!(min(&values), 0.0);
+		assert_eq!(max(&values), 40.0);
+		assert_eq!(percentile(&values, 0.0), 0.0);
+		assert_eq!(percentile(&values, 100.0), 40.0);
+		assert_eq!(percentile(&values, 5.0), 2.0);
+		assert_eq!(percentile(&values, 95.0), 38.0);
+
+		assert_eq!(median(&[1, 2]), 1.5);
+		assert_eq!(median(&[7]), 7.0);
+	}
+
+	#[test]
+	fn test_mad() {
+		assert_eq!(mad(&[1, 1, 2, 2, 4, 6, 9]), 1.0);
+		assert_eq!(mad(&[1, 2]), 0.5);
+
+		// A single outlier barely moves the MAD, unlike the stddev
+		let values = [100, 101, 99, 100, 100_000];
+		assert_eq!(mad(&values), 1.0);
+		assert_eq!(Spread::Rmad.of(&values), 1.0);
+		assert!(Spread::Rsd.of(&values) > 200.0);
+	}
+
+	#[test]
+	fn test_outliers() {
+		assert_eq!(outliers(&[100, 101, 99, 100, 100_000]), vec![4]);
+		assert_eq!(outliers(&[5, 1000, 1001, 999, 1000, 1002]), vec![0]);
+		assert_eq!(outliers(&[10, 20, 30, 40, 50]), Vec::<usize>::new());
+
+		// Differences of a single count are never outliers
+		assert_eq!(outliers(&[67, 67, 67, 67, 68]), Vec::<usize>::new());
+		assert_eq!(outliers(&[0, 0, 0, 0, 3]), vec![4]);
+
+		// Too few values to classify
+		assert_eq!(outliers(&[1, 1, 1_000]), Vec::<usize>::new());
+	}
+
+	#[test]
+	fn test_center_spread() {
+		let values = [0, 10, 20, 30, 40];
+
+		assert_eq!("mean".parse::<Center>().unwrap().of(&values), 20.0);
+		assert_eq!("median".parse::<Center>().unwrap().of(&values), 20.0);
+		assert_eq!("min".parse::<Center>().unwrap().of(&values), 0.0);
+		assert_eq!("max".parse::<Center>().unwrap().of(&values), 40.0);
+		assert_eq!("p5".parse::<Center>().unwrap().of(&values), 2.0);
+		assert_eq!("p95".parse::<Center>().unwrap().of(&values), 38.0);
+		assert!("nope".parse::<Center>().is_err());
+
+		assert_eq!("stddev".parse::<Spread>().unwrap().of(&values), stddev(&values));
+		assert_eq!("mad".parse::<Spread>().unwrap().of(&values), 10.0);
+		assert_eq!("rmad".parse::<Spread>().unwrap().of(&values), 50.0);
+		assert_eq!("rsd".parse::<Spread>().unwrap().of(&values), rsd(&values));
+		assert!("nope".parse::<Spread>().is_err());
+
+		assert!(Spread::Rsd.is_relative());
+		assert!(!Spread::Mad.is_relative());
+	}
+
+	#[test]
+	fn test_empty() {
+		assert_eq!(mean(&[]), 0.0);
+		assert_eq!(variance(&[]), 0.0);
+		assert_eq!(stddev(&[]), 0.0);
+		assert_eq!(rsd(&[]), 0.0);
+		assert_eq!(rsd(&[0, 0]), 0.0);
+		assert_eq!(median(&[]), 0.0);
+		assert_eq!(mad(&[]), 0.0);
+		assert_eq!(Spread::Rmad.of(&[0, 0]), 0.0);
+	}
+}